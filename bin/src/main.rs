@@ -1,15 +1,34 @@
-use clap::{Arg, ArgMatches, Command};
-use matahatan_lib::{run_simulation, Config, MazeKind};
+use clap::parser::ValueSource;
+use clap::{value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use matahatan_lib::{
+    evaluate, maze_from_spec, run_analysis, run_simulation, save_gif, save_png, write_maze_as, Bot,
+    ConfigFile, EvalConfig, GamepadMapping, Limits, MazeFormat, MazeKind, MazeSection, MazeSpec,
+    PhysicsProfile, Racer, Recording, Scene, ScoreConfig, ScoreMode, Transport, MAX_MAZE_SIZE,
+    MAX_PIXELS, MAX_ROOMS, MAX_STAIRS,
+};
 use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let mut command = Command::new("Matahatan")
         .version("0.1")
         .author("Adfinis AG")
         .about("Virtual Maze Solving Challenge")
-        .subcommand(
+        .subcommand(maze_args(score_args(limit_args(
             Command::new("simulate")
                 .about("Test/train your maze-solver")
+                .arg(
+                    Arg::new("config")
+                        .short('c')
+                        .long("config")
+                        .value_name("FILE")
+                        .help("Read the settings from a TOML file, options given here override it")
+                        .num_args(1),
+                )
                 .arg(
                     Arg::new("lua")
                         .short('l')
@@ -22,6 +41,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                     Arg::new("fps")
                         .short('f')
                         .long("fps")
+                        .value_parser(non_negative)
                         .default_value("25")
                         .value_name("FPS")
                         .help("FPS of the simulation not the GUI (0 as fast as possible)")
@@ -34,6 +54,13 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .help("Do not run GUI (unattended training) sets FPS to 0")
                         .num_args(0),
                 )
+                .arg(
+                    Arg::new("gui")
+                        .long("gui")
+                        .help("Run the GUI even if the config file turns it off")
+                        .conflicts_with("no-gui")
+                        .num_args(0),
+                )
                 .arg(
                     Arg::new("stdio")
                         .short('o')
@@ -41,6 +68,16 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .help("Run the simulation in stdio-mode (disables FPS)")
                         .num_args(0),
                 )
+                .arg(
+                    Arg::new("solver")
+                        .long("solver")
+                        .value_name("CMD")
+                        .help(
+                            "Spawn a solver process speaking the stdio protocol (run \
+                             with sh -c), its stderr is shown in the GUI",
+                        )
+                        .num_args(1),
+                )
                 .arg(
                     Arg::new("stick")
                         .short('s')
@@ -49,24 +86,296 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .num_args(0),
                 )
                 .arg(
-                    Arg::new("maze-seed")
-                        .short('m')
-                        .long("maze-seed")
-                        .value_name("MAZE-SEED")
-                        .default_value("")
-                        .help("Maze seed (any string)")
+                    Arg::new("gamepad")
+                        .long("gamepad")
+                        .value_name("FILE")
+                        .help("Gamepad mapping as JSON (axes, buttons, dead zones, curves)")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("keyboard")
+                        .long("keyboard")
+                        .help("Drive with the keyboard in the GUI (arrow keys or WASD)")
+                        .conflicts_with("no-gui")
+                        .num_args(0),
+                )
+                .arg(
+                    Arg::new("physics")
+                        .long("physics")
+                        .value_name("FILE")
+                        .help("Physics profile as JSON (missing fields are defaults)")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("bot")
+                        .long("bot")
+                        .value_name("BOT")
+                        .value_parser(["reference"])
+                        .help("Let a built-in bot drive (baseline frame count)")
+                        .num_args(1),
+                )
+                .arg(
+                    switch(Arg::new("ratio"))
+                        .long("ratio")
+                        .help("Report frames relative to a near lower bound for the maze"),
+                )
+                .arg(
+                    Arg::new("racer")
+                        .long("racer")
+                        .value_parser(racer)
+                        .value_name("RACER")
+                        .help(
+                            "Add a mouse: bot:reference, tcp:ADDRESS (wait for a \
+                             connection), stick:ID (controller) or a solver command \
+                             (repeatable)",
+                        )
+                        .action(ArgAction::Append)
+                        .num_args(1),
+                )
+                .arg(
+                    switch(Arg::new("mouse-collisions"))
+                        .long("mouse-collisions")
+                        .help("Mice collide with each other"),
+                )
+                .arg(
+                    switch(Arg::new("paused"))
+                        .long("paused")
+                        .help("Start paused (play/step in the GUI)"),
+                )
+                .arg(
+                    Arg::new("record")
+                        .long("record")
+                        .value_name("FILE")
+                        .help("Record the run of the first mouse (JSON lines)")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("ghost")
+                        .long("ghost")
+                        .value_name("FILE")
+                        .help("Show a recorded run as ghost (or the stdout of a stdio-run)")
+                        .num_args(1),
+                )
+                // One way to drive the first mouse
+                .group(
+                    ArgGroup::new("driver").args(["stdio", "stick", "keyboard", "solver", "bot"]),
+                ),
+        ))))
+        .subcommand(maze_args(
+            Command::new("analyze")
+                .about("Print metrics of a maze as JSON (path, turns, ...)")
+                .arg(
+                    Arg::new("config")
+                        .short('c')
+                        .long("config")
+                        .value_name("FILE")
+                        .help("Take maze and physics from a simulate config file, options given here override it")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("physics")
+                        .long("physics")
+                        .value_name("FILE")
+                        .help("Physics profile as JSON (missing fields are defaults)")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("optimize")
+                        .long("optimize")
+                        .help("Also tune the reference bot for the maze (optimized_frames, slower)")
+                        .num_args(0),
+                ),
+        ))
+        .subcommand(maze_feature_args(
+            score_args(limit_args(Command::new("eval")))
+                // A tournament should not hang on a single maze
+                .mut_arg("timeout", |arg| arg.default_value("60"))
+                .mut_arg("max-frames", |arg| arg.default_value("10000"))
+                .about("Run a solver on many mazes in parallel and report the results")
+                .arg(
+                    Arg::new("solver")
+                        .long("solver")
+                        .value_name("CMD")
+                        .help("Solver command speaking the stdio protocol (run with sh -c)")
+                        .required(true)
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("seeds")
+                        .long("seeds")
+                        .value_name("SEEDS")
+                        .help("Comma separated maze seeds")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("seed-range")
+                        .long("seed-range")
+                        .value_parser(seed_range)
+                        .value_name("FROM..TO")
+                        .help("Numbered maze seeds, TO excluded (0..100)")
                         .num_args(1),
                 )
+                .group(
+                    ArgGroup::new("seed-list")
+                        .args(["seeds", "seed-range"])
+                        .required(true),
+                )
                 .arg(
-                    Arg::new("maze-kind")
+                    Arg::new("maze-kinds")
                         .short('k')
-                        .long("maze-kind")
-                        .value_name("MAZE-KIND")
+                        .long("maze-kinds")
+                        .value_parser(maze_kinds)
+                        .value_name("KINDS")
                         .default_value("backtracking")
-                        .help("Maze kind ('ellers', 'backtracking', 'growing_tree', 'prims')")
+                        .help("Comma separated maze kinds, every seed is run with each kind")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("physics")
+                        .long("physics")
+                        .value_name("FILE")
+                        .help("Physics profile as JSON (missing fields are defaults)")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .short('j')
+                        .long("jobs")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .value_name("N")
+                        .help("Mazes to run in parallel [default: number of CPUs]")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .value_parser(["csv", "json"])
+                        .default_value("csv")
+                        .help("Report format")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Write the report to FILE instead of stdout")
                         .num_args(1),
+                )
+                .arg(
+                    Arg::new("ratio")
+                        .long("ratio")
+                        .help("Add a near lower bound of the frames and the ratio to it")
+                        .num_args(0),
                 ),
-        )
+        ))
+        .subcommand(maze_args(
+            Command::new("generate")
+                .about("Print a maze without running it (JSON header, ASCII or .maz)")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .value_parser(MazeFormat::from_str)
+                        .default_value("json")
+                        .help("json (solver header and hash), ascii or maz (micromouse)")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Write the maze to FILE instead of stdout")
+                        .num_args(1),
+                ),
+        ))
+        .subcommand(maze_args(
+            Command::new("render")
+                .about("Draw a maze and optionally a recorded run to PNG or SVG")
+                .arg(
+                    Arg::new("replay")
+                        .long("replay")
+                        .value_name("FILE")
+                        .help("Recorded run to draw (--record or the stdout of a stdio-run)")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Picture to write, SVG if it ends in .svg, PNG otherwise")
+                        .required(true)
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("cell")
+                        .long("cell")
+                        .value_parser(pixels)
+                        .value_name("PIXELS")
+                        .default_value("24")
+                        .help("Size of a cell in pixels")
+                        .num_args(1),
+                ),
+        ))
+        .subcommand(maze_args(
+            Command::new("animate")
+                .about("Turn a recorded run into an animated GIF or PNG frames")
+                .arg(
+                    Arg::new("replay")
+                        .long("replay")
+                        .value_name("FILE")
+                        .help("Recorded run (--record or the stdout of a stdio-run)")
+                        .required(true)
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("PATH")
+                        .help("GIF if it ends in .gif, otherwise a directory for numbered PNGs")
+                        .required(true)
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("stride")
+                        .long("stride")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .value_name("FRAMES")
+                        .default_value("1")
+                        .help("Draw every FRAMES-th frame (and the last)")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("cell")
+                        .long("cell")
+                        .value_parser(pixels)
+                        .value_name("PIXELS")
+                        .default_value("16")
+                        .help("Size of a cell in pixels")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("width")
+                        .long("width")
+                        .value_parser(pixels)
+                        .value_name("PIXELS")
+                        .help("Width of the picture, overrides --cell")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("fps")
+                        .short('f')
+                        .long("fps")
+                        .value_parser(positive)
+                        .value_name("FPS")
+                        .default_value("25")
+                        .help("Simulation frames per second of the GIF")
+                        .num_args(1),
+                ),
+        ))
         .subcommand(
             Command::new("server")
                 .about("Run the maze-solver server (the actual challenges will be missing)"),
@@ -74,45 +383,546 @@ fn main() -> Result<(), Box<dyn Error>> {
     let matches = command.clone().get_matches();
 
     match matches.subcommand() {
-        Some(("simulate", m)) => simulate(m),
+        Some(("simulate", m)) => simulate(m)?,
+        Some(("analyze", m)) => analyze(m)?,
+        Some(("eval", m)) => eval(m)?,
+        Some(("generate", m)) => generate(m)?,
+        Some(("render", m)) => render(m)?,
+        Some(("animate", m)) => animate(m)?,
         Some(("server", _m)) => (),
         _ => command.print_long_help()?,
     }
     Ok(())
 }
 
-fn simulate(m: &ArgMatches) {
-    let gui = !m.get_flag("no-gui");
-    let stick = m.get_flag("stick");
-    let stdio = m.get_flag("stdio") && !stick;
-    let framerate: f32 = match m.get_one::<String>("fps") {
-        Some(fps_str) => fps_str.parse().unwrap_or(25.0_f32),
-        None => 25.0_f32,
+// Options that a maze id replaces
+const MAZE_OPTIONS: [&str; 6] = [
+    "maze-seed",
+    "maze-kind",
+    "maze-size",
+    "braid",
+    "rooms",
+    "stairs",
+];
+
+fn maze_args(command: Command) -> Command {
+    let command = command
+        .arg(
+            Arg::new("maze-seed")
+                .short('m')
+                .long("maze-seed")
+                .value_name("MAZE-SEED")
+                .default_value("")
+                .help("Maze seed (any string)")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("maze-kind")
+                .short('k')
+                .long("maze-kind")
+                .value_parser(MazeKind::from_str)
+                .value_name("MAZE-KIND")
+                .default_value("backtracking")
+                .help("Maze kind ('ellers', 'backtracking', 'growing_tree', 'prims')")
+                .num_args(1),
+        );
+    maze_feature_args(command).arg(
+        Arg::new("maze-id")
+            .long("maze-id")
+            .value_parser(MazeSpec::from_str)
+            .value_name("ID")
+            .conflicts_with_all(MAZE_OPTIONS)
+            .help("Maze id as shown in the GUI and the header, replaces the other maze options")
+            .num_args(1),
+    )
+}
+
+fn maze_feature_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("maze-size")
+                .long("maze-size")
+                .value_parser(maze_size_parser)
+                .value_name("WxH")
+                .default_value("25x25")
+                .help("Width and height of the maze in cells")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("braid")
+                .short('b')
+                .long("braid")
+                .value_parser(fraction)
+                .value_name("FRACTION")
+                .default_value("0")
+                .help("Fraction of dead ends to remove, adds loops (0.0 to 1.0)")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("rooms")
+                .short('r')
+                .long("rooms")
+                .value_parser(value_parser!(u32).range(..=MAX_ROOMS as i64))
+                .value_name("COUNT")
+                .default_value("0")
+                .help("Number of open rooms to carve into the maze")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("stairs")
+                .short('t')
+                .long("stairs")
+                .value_parser(value_parser!(u32).range(..=MAX_STAIRS as i64))
+                .value_name("COUNT")
+                .default_value("0")
+                .help("Number of zig-zag staircases (diagonal sections) to carve")
+                .num_args(1),
+        )
+}
+
+fn limit_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("max-frames")
+                .long("max-frames")
+                .value_parser(value_parser!(i64).range(0..))
+                .value_name("FRAMES")
+                .help("End the run after FRAMES frames")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("max-collisions")
+                .long("max-collisions")
+                .value_parser(value_parser!(i64).range(0..))
+                .value_name("COUNT")
+                .help("End the run after COUNT separate wall contacts")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("max-collision-frames")
+                .long("max-collision-frames")
+                .value_parser(value_parser!(i64).range(0..))
+                .value_name("FRAMES")
+                .help("End the run after FRAMES frames touching a wall")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_parser(positive)
+                .value_name("SECS")
+                .help("Wall-clock time for the whole run")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("frame-timeout")
+                .long("frame-timeout")
+                .value_parser(positive)
+                .value_name("SECS")
+                .help("Wall-clock time the solver has to answer a frame")
+                .num_args(1),
+        )
+}
+
+fn score_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("score")
+                .long("score")
+                .value_name("MODE")
+                .value_parser(["frames", "penalty", "disqualify"])
+                .default_value("frames")
+                .help("Score: frames, frames plus penalties or disqualify on collision")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("collision-penalty")
+                .long("collision-penalty")
+                .value_parser(non_negative)
+                .value_name("FRAMES")
+                .default_value("10")
+                .help("Penalty frames per wall contact")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("contact-penalty")
+                .long("contact-penalty")
+                .value_parser(non_negative)
+                .value_name("FRAMES")
+                .default_value("1")
+                .help("Penalty frames per frame touching a wall")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("off-course-penalty")
+                .long("off-course-penalty")
+                .value_parser(non_negative)
+                .value_name("FRAMES")
+                .default_value("100")
+                .help("Penalty frames per time leaving the maze")
+                .num_args(1),
+        )
+}
+
+fn score(m: &ArgMatches) -> ScoreConfig {
+    let mode = match m.get_one::<String>("score").map(|s| s.as_str()) {
+        Some("penalty") => ScoreMode::Penalty,
+        Some("disqualify") => ScoreMode::Disqualify,
+        _ => ScoreMode::Frames,
+    };
+    ScoreConfig {
+        mode,
+        collision_penalty: value(m, "collision-penalty"),
+        contact_penalty: value(m, "contact-penalty"),
+        off_course_penalty: value(m, "off-course-penalty"),
+    }
+}
+
+fn limits(m: &ArgMatches) -> Limits {
+    let seconds = |name| m.get_one::<f32>(name).copied().map(Duration::from_secs_f32);
+    Limits {
+        max_frames: m.get_one::<i64>("max-frames").copied(),
+        max_collisions: m.get_one::<i64>("max-collisions").copied(),
+        max_collision_frames: m.get_one::<i64>("max-collision-frames").copied(),
+        timeout: seconds("timeout"),
+        frame_timeout: seconds("frame-timeout"),
+    }
+}
+
+// Value of an argument with a default, typed by its value parser
+fn value<T: Clone + Send + Sync + 'static>(m: &ArgMatches, name: &str) -> T {
+    m.get_one::<T>(name).cloned().unwrap()
+}
+
+// Value parsers, clap prints the error with the argument
+fn non_negative(value_str: &str) -> Result<f32, String> {
+    match value_str.parse::<f32>() {
+        Ok(number) if number.is_finite() && number >= 0.0 => Ok(number),
+        _ => Err("expected a number of at least 0".to_string()),
+    }
+}
+
+fn positive(value_str: &str) -> Result<f32, String> {
+    match value_str.parse::<f32>() {
+        Ok(number) if number.is_finite() && number > 0.0 => Ok(number),
+        _ => Err("expected a number greater than 0".to_string()),
+    }
+}
+
+// Picture sizes, larger pictures are refused when drawing as well
+fn pixels(value_str: &str) -> Result<f32, String> {
+    match value_str.parse::<f32>() {
+        Ok(number) if number > 0.0 && number <= MAX_PIXELS as f32 => Ok(number),
+        _ => Err(format!(
+            "expected pixels greater than 0 and at most {MAX_PIXELS}"
+        )),
+    }
+}
+
+fn fraction(value_str: &str) -> Result<f32, String> {
+    match value_str.parse::<f32>() {
+        Ok(number) if (0.0..=1.0).contains(&number) => Ok(number),
+        _ => Err("expected a number from 0.0 to 1.0".to_string()),
+    }
+}
+
+fn maze_size_parser(size_str: &str) -> Result<(i32, i32), String> {
+    let size = size_str.split_once('x').and_then(|(width, height)| {
+        Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+    });
+    match size {
+        Some((width, height))
+            if (2..=MAX_MAZE_SIZE).contains(&width) && (2..=MAX_MAZE_SIZE).contains(&height) =>
+        {
+            Ok((width, height))
+        }
+        _ => Err(format!(
+            "expected WIDTHxHEIGHT, from 2x2 to {MAX_MAZE_SIZE}x{MAX_MAZE_SIZE} (25x25)"
+        )),
+    }
+}
+
+fn maze_kinds(kinds_str: &str) -> Result<Vec<MazeKind>, String> {
+    kinds_str
+        .split(',')
+        .map(|kind| kind.trim().parse())
+        .collect()
+}
+
+fn seed_range(range_str: &str) -> Result<(u64, u64), String> {
+    let range = range_str
+        .split_once("..")
+        .and_then(|(from, to)| Some((from.trim().parse().ok()?, to.trim().parse().ok()?)));
+    range.ok_or_else(|| "expected FROM..TO (0..100)".to_string())
+}
+
+fn racer(spec: &str) -> Result<String, String> {
+    match Racer::parse(spec) {
+        Some(_) => Ok(spec.to_string()),
+        None => Err("expected bot:reference, tcp:ADDRESS, stick:ID or a command".to_string()),
+    }
+}
+
+fn maze_seed(m: &ArgMatches) -> Option<String> {
+    m.get_one::<String>("maze-seed")
+        .filter(|seed| !seed.is_empty())
+        .cloned()
+}
+
+fn maze_spec(m: &ArgMatches) -> MazeSpec {
+    if let Some(spec) = m.get_one::<MazeSpec>("maze-id") {
+        return spec.clone();
+    }
+    MazeSpec::new(&maze_seed(m), value(m, "maze-kind"))
+        .with_size(value(m, "maze-size"))
+        .with_braid(value(m, "braid"))
+        .with_rooms(value(m, "rooms"))
+        .with_stairs(value(m, "stairs"))
+}
+
+// Value typed on the command line, the defaults of clap do not count
+fn given<T: Clone + Send + Sync + 'static>(m: &ArgMatches, name: &str) -> Option<T> {
+    match m.value_source(name) {
+        Some(ValueSource::CommandLine) => m.get_one::<T>(name).cloned(),
+        _ => None,
+    }
+}
+
+// A flag that takes =false as well, to switch off what the config file
+// switches on
+fn switch(arg: Arg) -> Arg {
+    arg.value_name("BOOL")
+        .value_parser(value_parser!(bool))
+        .num_args(0..=1)
+        .require_equals(true)
+        .default_missing_value("true")
+        .hide_possible_values(true)
+}
+
+fn simulate(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let mut file = match m.get_one::<String>("config") {
+        Some(path) => ConfigFile::load(path)?,
+        None => ConfigFile::default(),
+    };
+    override_config(&mut file, m)?;
+    run_simulation(&file.config()?)?;
+    Ok(())
+}
+
+// The command line wins over the configuration file
+fn override_config(file: &mut ConfigFile, m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    if m.get_flag("no-gui") {
+        file.gui = Some(false);
+    } else if m.get_flag("gui") {
+        file.gui = Some(true);
+    }
+    file.fps = given(m, "fps").or(file.fps);
+    // A mouse driver given here replaces the one of the file
+    let transport = if m.get_flag("stick") {
+        Some(Transport::Stick)
+    } else if m.get_flag("keyboard") {
+        Some(Transport::Keyboard)
+    } else if m.get_flag("stdio") {
+        Some(Transport::Stdio)
+    } else {
+        None
     };
-    let kind = match m.get_one::<String>("maze-kind") {
-        Some(kind_str) => match kind_str.as_str() {
-            "ellers" => MazeKind::Ellers,
-            "backtracking" => MazeKind::Backtracking,
-            "growing_tree" => MazeKind::GrowingTree,
-            "prims" => MazeKind::Prims,
-            _ => MazeKind::Backtracking,
-        },
-        None => MazeKind::Backtracking,
+    let solver = given::<String>(m, "solver");
+    let bot = match given::<String>(m, "bot").as_deref() {
+        Some("reference") => Some(Bot::Reference),
+        _ => None,
+    };
+    if transport.is_some() || solver.is_some() || bot.is_some() {
+        file.transport = transport;
+        file.solver = solver;
+        file.bot = bot;
+    }
+    if m.value_source("racer") == Some(ValueSource::CommandLine) {
+        let racers = m.get_many::<String>("racer").unwrap_or_default();
+        file.racers = Some(racers.cloned().collect());
+    }
+    file.mouse_collisions = given(m, "mouse-collisions").or(file.mouse_collisions);
+    file.ratio = given(m, "ratio").or(file.ratio);
+    file.paused = given(m, "paused").or(file.paused);
+    file.record = given(m, "record").or(file.record.take());
+    file.ghost = given(m, "ghost").or(file.ghost.take());
+    override_maze(&mut file.maze, m);
+    if let Some(path) = m.get_one::<String>("physics") {
+        file.physics = Some(PhysicsProfile::load(path)?);
+    }
+    let limits = &mut file.limits;
+    limits.max_frames = given(m, "max-frames").or(limits.max_frames);
+    limits.max_collisions = given(m, "max-collisions").or(limits.max_collisions);
+    limits.max_collision_frames = given(m, "max-collision-frames").or(limits.max_collision_frames);
+    limits.timeout = given(m, "timeout").or(limits.timeout);
+    limits.frame_timeout = given(m, "frame-timeout").or(limits.frame_timeout);
+    let score = file.score.get_or_insert_with(ScoreConfig::default);
+    score.mode = match given::<String>(m, "score").as_deref() {
+        Some("frames") => ScoreMode::Frames,
+        Some("penalty") => ScoreMode::Penalty,
+        Some("disqualify") => ScoreMode::Disqualify,
+        _ => score.mode,
     };
-    let seed = match m.get_one::<String>("maze-seed") {
-        Some(seed_str) => match seed_str.as_str() {
-            "" => None,
-            _ => Some(seed_str.clone()),
-        },
+    score.collision_penalty = given(m, "collision-penalty").unwrap_or(score.collision_penalty);
+    score.contact_penalty = given(m, "contact-penalty").unwrap_or(score.contact_penalty);
+    score.off_course_penalty = given(m, "off-course-penalty").unwrap_or(score.off_course_penalty);
+    if let Some(path) = m.get_one::<String>("gamepad") {
+        file.gamepad = Some(GamepadMapping::load(path)?);
+    }
+    Ok(())
+}
+
+fn seeds(m: &ArgMatches) -> Vec<String> {
+    if let Some(seeds) = m.get_one::<String>("seeds") {
+        return seeds.split(',').map(|seed| seed.to_string()).collect();
+    }
+    let (from, to) = value::<(u64, u64)>(m, "seed-range");
+    (from..to).map(|seed| seed.to_string()).collect()
+}
+
+// Maze options given on the command line replace those of the config file
+fn override_maze(maze: &mut MazeSection, m: &ArgMatches) {
+    if let Some(spec) = given::<MazeSpec>(m, "maze-id") {
+        *maze = MazeSection {
+            id: Some(spec.to_string()),
+            ..MazeSection::default()
+        };
+    }
+    // Any other maze option replaces the id of the file
+    if MAZE_OPTIONS
+        .iter()
+        .any(|name| m.value_source(name) == Some(ValueSource::CommandLine))
+    {
+        maze.id = None;
+    }
+    maze.seed = given(m, "maze-seed").or(maze.seed.take());
+    maze.kind = given(m, "maze-kind").or(maze.kind);
+    maze.size = given(m, "maze-size").or(maze.size);
+    maze.braid = given(m, "braid").or(maze.braid);
+    maze.rooms = given(m, "rooms").or(maze.rooms);
+    maze.stairs = given(m, "stairs").or(maze.stairs);
+}
+
+fn analyze(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let mut file = match m.get_one::<String>("config") {
+        Some(path) => ConfigFile::load(path)?,
+        None => ConfigFile::default(),
+    };
+    override_maze(&mut file.maze, m);
+    if let Some(path) = m.get_one::<String>("physics") {
+        file.physics = Some(PhysicsProfile::load(path)?);
+    }
+    let config = file.config()?;
+    run_analysis(&config.maze_spec(), &config.physics, m.get_flag("optimize"));
+    Ok(())
+}
+
+fn eval(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let physics = match m.get_one::<String>("physics") {
+        Some(path) => PhysicsProfile::load(path)?,
+        None => PhysicsProfile::default(),
+    };
+    let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+    let config = EvalConfig {
+        solver: m.get_one::<String>("solver").unwrap().clone(),
+        seeds: seeds(m),
+        kinds: value(m, "maze-kinds"),
+        size: value(m, "maze-size"),
+        braid: value(m, "braid"),
+        rooms: value(m, "rooms"),
+        stairs: value(m, "stairs"),
+        physics,
+        jobs: m.get_one::<u64>("jobs").map_or(cpus, |jobs| *jobs as usize),
+        limits: limits(m),
+        score: score(m),
+        ratio: m.get_flag("ratio"),
+    };
+    let report = evaluate(&config);
+    let mut writer: Box<dyn Write> = match m.get_one::<String>("output") {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    if m.get_one::<String>("format").map(|s| s.as_str()) == Some("json") {
+        report.write_json(&mut writer)?;
+    } else {
+        report.write_csv(&mut writer)?;
+        report.summary.write_json(&mut io::stderr())?;
+    }
+    Ok(())
+}
+
+fn generate(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let spec = maze_spec(m);
+    let format: MazeFormat = value(m, "format");
+    let mut writer: Box<dyn Write> = match m.get_one::<String>("output") {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    write_maze_as(&mut writer, &spec, format)?;
+    // JSON has the hash in it
+    if format != MazeFormat::Json {
+        eprintln!("{}", spec.hash());
+    }
+    Ok(())
+}
+
+// The maze of a recording unless maze options are given, they have to be its
+fn replay_maze(m: &ArgMatches, recording: &Recording) -> io::Result<MazeSpec> {
+    let given = MAZE_OPTIONS
+        .iter()
+        .chain(&["maze-id"])
+        .any(|name| m.value_source(name) == Some(ValueSource::CommandLine));
+    match &recording.maze {
+        Some(spec) if !given => Ok(spec.clone()),
+        _ => {
+            let spec = maze_spec(m);
+            recording.check(&spec)?;
+            Ok(spec)
+        }
+    }
+}
+
+fn render(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let recording = match m.get_one::<String>("replay") {
+        Some(path) => Some(Recording::load(path)?),
         None => None,
     };
-    let config = Config {
-        gui,
-        stdio,
-        stick,
-        framerate,
-        kind,
-        seed,
+    let spec = match &recording {
+        Some(recording) => replay_maze(m, recording)?,
+        None => maze_spec(m),
     };
-    run_simulation(&config);
+    let maze = maze_from_spec(&spec);
+    let mut scene = Scene::new(&maze, value(m, "cell"));
+    if let Some(recording) = recording {
+        scene.trail(&recording.frames);
+        if let Some(last) = recording.frames.last() {
+            scene.car(last);
+        }
+    }
+    scene.save(m.get_one::<String>("output").unwrap())?;
+    Ok(())
+}
+
+fn animate(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let recording = Recording::load(m.get_one::<String>("replay").unwrap())?;
+    let maze = maze_from_spec(&replay_maze(m, &recording)?);
+    let cell = match m.get_one::<f32>("width") {
+        Some(width) => width / (maze.size.0 + 1) as f32,
+        None => value(m, "cell"),
+    };
+    let stride = value::<u64>(m, "stride") as usize;
+    let pictures = Scene::animation(&maze, &recording.frames, cell, stride)?;
+    let output = m.get_one::<String>("output").unwrap();
+    if output.to_lowercase().ends_with(".gif") {
+        // GIF delays are in 1/100 s
+        let fps: f32 = value(m, "fps");
+        let delay = (100.0 * stride as f32 / fps).round().max(2.0) as u16;
+        save_gif(output, pictures.map(|(_, pixmap)| pixmap), delay)?;
+    } else {
+        fs::create_dir_all(output)?;
+        for (frame, pixmap) in pictures {
+            save_png(&format!("{output}/frame_{frame:05}.png"), &pixmap)?;
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,173 @@
+use super::maze::{Coordinates, Direction, Maze};
+use super::{lower_bound, PhysicsProfile};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+const MAX_ESTIMATE_FRAMES: u64 = 1_000_000;
+
+// Path metrics are taken on the shortest path from start to goal, lengths are
+// in cells travelled.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Analysis {
+    pub width: i32,
+    pub height: i32,
+    pub path_length: usize,
+    pub turns: usize,
+    pub dead_ends: usize,
+    pub longest_straight: usize,
+    pub branching_factor: f32,
+    pub estimated_frames: u64,
+    // Near lower bound and the tuned reference bot (--optimize) around the
+    // frames a solver can reach
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bound: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optimized_frames: Option<i64>,
+}
+
+pub fn analyze(maze: &Maze, physics: &PhysicsProfile) -> Analysis {
+    let path = shortest_path(maze).unwrap_or_default();
+    let runs = straight_runs(&path);
+    Analysis {
+        width: maze.size.0,
+        height: maze.size.1,
+        path_length: path.len().saturating_sub(1),
+        turns: runs.len().saturating_sub(1),
+        dead_ends: maze.dead_ends().len(),
+        longest_straight: runs.iter().copied().max().unwrap_or(0),
+        branching_factor: branching_factor(maze),
+        estimated_frames: estimate_frames(&path, physics),
+        bound: lower_bound(maze, physics),
+        optimized_frames: None,
+    }
+}
+
+// Breadth-first search through the passages, start and goal included.
+pub fn shortest_path(maze: &Maze) -> Option<Vec<Coordinates>> {
+    let width = maze.size.0;
+    let index = |c: &Coordinates| (c.y * width + c.x) as usize;
+    let mut parent: Vec<Option<Coordinates>> = vec![None; (maze.size.0 * maze.size.1) as usize];
+    let mut queue = VecDeque::new();
+    parent[index(&maze.start)] = Some(maze.start);
+    queue.push_back(maze.start);
+    while let Some(current) = queue.pop_front() {
+        if current == maze.goal {
+            let mut path = vec![current];
+            let mut cell = current;
+            while cell != maze.start {
+                cell = parent[index(&cell)]?;
+                path.push(cell);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for direction in Direction::all() {
+            let next = current.next(&direction);
+            if maze.has_passage(&current, &direction) && parent[index(&next)].is_none() {
+                parent[index(&next)] = Some(current);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+// Number of cells travelled in each straight section of the path.
+pub fn straight_runs(path: &[Coordinates]) -> Vec<usize> {
+    let mut runs: Vec<usize> = Vec::new();
+    let mut last = None;
+    for step in path.windows(2) {
+        let direction = (step[1].x - step[0].x, step[1].y - step[0].y);
+        match runs.last_mut() {
+            Some(run) if last == Some(direction) => *run += 1,
+            _ => runs.push(1),
+        }
+        last = Some(direction);
+    }
+    runs
+}
+
+// Average number of onward choices in cells that are not dead ends.
+fn branching_factor(maze: &Maze) -> f32 {
+    let mut cells = 0;
+    let mut choices = 0;
+    for ix in 0..maze.size.0 {
+        for iy in 0..maze.size.1 {
+            if let Some(field) = maze.get_field(&(ix, iy).into()) {
+                let count = field.passage_count();
+                if count >= 2 {
+                    cells += 1;
+                    choices += count - 1;
+                }
+            }
+        }
+    }
+    if cells == 0 {
+        0.0
+    } else {
+        choices as f32 / cells as f32
+    }
+}
+
+// Highest velocity at which the mouse can still turn within half a cell.
+pub fn corner_velocity(physics: &PhysicsProfile) -> f32 {
+    turn_velocity(0.5, physics)
+}
+
+// Highest velocity for an arc of radius (in cells), above 1 / turn_scaler the
+// mouse turns slower the faster it is (vel_scale).
+pub fn turn_velocity(radius: f32, physics: &PhysicsProfile) -> f32 {
+    let slow = radius * physics.steering_scaler;
+    let velocity = if slow * physics.turn_scaler <= 1.0 {
+        slow
+    } else {
+        (radius * physics.steering_scaler / physics.turn_scaler).sqrt()
+    };
+    velocity.min(physics.max_velocity)
+}
+
+// Rough estimate: full throttle on straights, braking to corner velocity
+// before each turn. Ignores the turn itself and the starting angle.
+pub fn estimate_frames(path: &[Coordinates], physics: &PhysicsProfile) -> u64 {
+    let runs = straight_runs(path);
+    let corner = corner_velocity(physics);
+    let mut velocity = 0.0;
+    let mut frames = 0;
+    for (i, run) in runs.iter().enumerate() {
+        let last = i == runs.len() - 1;
+        // The goal is reached when entering the goal cell
+        let (distance, target) = if last {
+            (*run as f32 - 0.5, physics.max_velocity)
+        } else {
+            (*run as f32, corner)
+        };
+        let (segment, exit) = segment_frames(distance, velocity, target, physics);
+        frames += segment;
+        velocity = exit;
+    }
+    frames
+}
+
+fn segment_frames(
+    distance: f32,
+    velocity: f32,
+    target: f32,
+    physics: &PhysicsProfile,
+) -> (u64, f32) {
+    let brake = physics.acceleration_scaler * physics.brake_scaler;
+    let mut velocity = velocity;
+    let mut position = 0.0;
+    let mut frames = 0;
+    while position < distance && frames < MAX_ESTIMATE_FRAMES {
+        let remaining = distance - position;
+        let braking = (velocity * velocity - target * target) / (2.0 * brake);
+        if velocity > target && braking >= remaining {
+            velocity = (velocity - brake).max(target);
+        } else {
+            velocity = (velocity + physics.acceleration_scaler).min(physics.max_velocity);
+        }
+        position += velocity;
+        frames += 1;
+    }
+    (frames, velocity)
+}
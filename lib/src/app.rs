@@ -1,15 +1,171 @@
-use super::{maze_from_seed_and_kind, MazeSpec, SharedState, SimulationState};
-use egui::{vec2, Color32, Pos2, Rect, RichText, Rounding, Shape, Stroke, Ui, Vec2};
-use maze_generator::prelude::*;
+use super::gamepad::{button_name, Axis, Curve};
+use super::maze::{Maze, Wall};
+use super::replay::cell_index;
+use super::{
+    maze_from_spec, GamepadMapping, Keys, MazeKind, MazeSpec, Mouse, Recording, ScoreConfig,
+    SharedState, SimulationState, TrailPoint, MAZE_SIZE,
+};
+use egui::{
+    vec2, Align2, Color32, FontId, Key, Pos2, Rect, Response, RichText, Rounding, Sense, Shape,
+    Stroke, Ui, Vec2,
+};
+use gamepads::{Button, Gamepads};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
 use std::sync::{Arc, Mutex};
 
-#[derive(serde::Deserialize, serde::Serialize)]
-#[serde(default)]
-pub struct MatahatanAppState {}
+// Mouse colours in racer order
+const COLORS: [Color32; 6] = [
+    Color32::LIGHT_YELLOW,
+    Color32::LIGHT_BLUE,
+    Color32::LIGHT_RED,
+    Color32::LIGHT_GREEN,
+    Color32::GOLD,
+    Color32::from_rgb(220, 160, 255),
+];
+
+// Preferences restored on startup, missing fields are defaults
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatahatanAppState {
+    theme: Theme,
+    show_side_panel: bool,
+    show_trail: bool,
+    // Points drawn per trail, 0 is the whole run
+    trail_length: usize,
+    show_heatmap: bool,
+    camera: Camera,
+    // Maze for the next restart, an empty seed is a random maze. Size is the
+    // one of the last maze, it is opened again when none is given.
+    seed: String,
+    kind: MazeKind,
+    size: (i32, i32),
+    // Plotted frames and where the telemetry of the first mouse is exported
+    telemetry_frames: usize,
+    telemetry_path: String,
+    plots: Vec<Plot>,
+    // Where the calibration saves the gamepad mapping, for --gamepad
+    gamepad_path: String,
+}
+
+impl MatahatanAppState {
+    // The maze of seed, kind and size unless the seed is a maze id, braid,
+    // rooms and stairs stay those of current
+    pub fn maze(&self, current: &MazeSpec) -> MazeSpec {
+        let seed = self.seed.trim();
+        if let Ok(spec) = seed.parse::<MazeSpec>() {
+            return spec;
+        }
+        let seed = Some(seed.to_string()).filter(|seed| !seed.is_empty());
+        current.with_seed(&seed, self.kind).with_size(self.size)
+    }
+}
 
 impl Default for MatahatanAppState {
     fn default() -> Self {
-        Self {}
+        MatahatanAppState {
+            theme: Theme::Dark,
+            show_side_panel: true,
+            show_trail: true,
+            trail_length: 0,
+            show_heatmap: false,
+            camera: Camera::default(),
+            seed: String::new(),
+            kind: MazeKind::Backtracking,
+            size: MAZE_SIZE,
+            telemetry_frames: 300,
+            telemetry_path: "telemetry.csv".to_string(),
+            plots: Plot::all().to_vec(),
+            gamepad_path: "gamepad.json".to_string(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Theme {
+    Dark,
+    Light,
+}
+
+impl Theme {
+    fn visuals(&self) -> egui::Visuals {
+        match self {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Plot {
+    Velocity,
+    TrueVelocity,
+    Steering,
+    Acceleration,
+    Angle,
+    Collision,
+}
+
+impl Plot {
+    fn all() -> [Plot; 6] {
+        [
+            Plot::Velocity,
+            Plot::TrueVelocity,
+            Plot::Steering,
+            Plot::Acceleration,
+            Plot::Angle,
+            Plot::Collision,
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Plot::Velocity => "Velocity",
+            Plot::TrueVelocity => "Velocity (true)",
+            Plot::Steering => "Steering",
+            Plot::Acceleration => "Acceleration",
+            Plot::Angle => "Angle (rad)",
+            Plot::Collision => "Collision",
+        }
+    }
+
+    fn value(&self, point: &TrailPoint) -> f32 {
+        match self {
+            Plot::Velocity => point.velocity,
+            Plot::TrueVelocity => point.true_velocity,
+            Plot::Steering => point.steering,
+            Plot::Acceleration => point.acceleration,
+            Plot::Angle => point.angle,
+            Plot::Collision => point.collision as i32 as f32,
+        }
+    }
+
+    // None scales to the values
+    fn range(&self, max_velocity: f32) -> Option<RangeInclusive<f32>> {
+        match self {
+            Plot::Velocity | Plot::TrueVelocity => Some(0.0..=max_velocity),
+            Plot::Steering | Plot::Acceleration => Some(-1.0..=1.0),
+            Plot::Angle => None,
+            Plot::Collision => Some(0.0..=1.0),
+        }
+    }
+}
+
+// A recorded run replayed in the same frame timeline as the first mouse
+struct Ghost {
+    recording: Recording,
+    entered: Vec<Option<i64>>,
+}
+
+impl Ghost {
+    fn new(recording: Recording, maze: &Maze) -> Self {
+        Ghost {
+            entered: recording.entered(maze),
+            recording,
+        }
     }
 }
 
@@ -20,11 +176,87 @@ struct MazeInfo {
     y: f32,
 }
 
+// Zoom 1 fits the whole maze into the panel, cells stay square
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+struct Camera {
+    zoom: f32,
+    // Maze point in the middle of the panel, None is the middle of the maze
+    #[serde(skip)]
+    center: Option<Vec2>,
+    follow: bool,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            zoom: 1.0,
+            center: None,
+            follow: false,
+        }
+    }
+}
+
+impl Camera {
+    // Wheel zooms around the pointer, dragging pans and stops following
+    fn update(&mut self, ui: &Ui, response: &Response, maze: &Maze, target: Option<Vec2>) {
+        let rect = response.rect;
+        let size = maze_size(maze);
+        let mut center = self.center.unwrap_or(size / 2.0);
+        let cell = self.cell(rect, size);
+        if response.dragged() {
+            center -= response.drag_delta() / cell;
+            self.follow = false;
+        }
+        if let Some(pointer) = response.hover_pos() {
+            let scroll = ui.input(|i| i.smooth_scroll_delta.y);
+            if scroll != 0.0 {
+                let offset = pointer - rect.center();
+                let point = center + offset / cell;
+                self.zoom = (self.zoom * (scroll * 0.002).exp()).clamp(0.5, 50.0);
+                center = point - offset / self.cell(rect, size);
+            }
+        }
+        if let (true, Some(target)) = (self.follow, target) {
+            center = target;
+        }
+        self.center = Some(center);
+    }
+
+    fn cell(&self, rect: Rect, size: Vec2) -> f32 {
+        let fit = ((rect.width() - 40.0) / size.x).min((rect.height() - 30.0) / size.y);
+        fit.max(1.0) * self.zoom
+    }
+
+    fn maze_info(&self, rect: Rect, maze: &Maze) -> MazeInfo {
+        let size = maze_size(maze);
+        let cell = self.cell(rect, size);
+        let center = self.center.unwrap_or(size / 2.0);
+        let border =
+            Rect::from_center_size(rect.center() + (size / 2.0 - center) * cell, size * cell);
+        MazeInfo {
+            border,
+            square: vec2(cell, cell),
+            x: size.x,
+            y: size.y,
+        }
+    }
+}
+
 pub struct MatahatanApp {
     maze: Maze,
     maze_spec: MazeSpec,
     shared_state: Arc<Mutex<SharedState>>,
     app_state: MatahatanAppState,
+    ghost: Option<Ghost>,
+    ghost_path: String,
+    trails: Vec<Vec<TrailPoint>>,
+    // Frames the first mouse spent in each cell, indexed like the maze
+    heat: Vec<i64>,
+    step_count: u32,
+    // Opened with the calibration window, the simulation polls its own
+    calibration: Option<Gamepads>,
+    run: u64,
 }
 
 enum FormatType {
@@ -35,38 +267,326 @@ enum FormatType {
 
 impl MatahatanApp {
     pub fn new(cc: &eframe::CreationContext<'_>, shared_state: Arc<Mutex<SharedState>>) -> Self {
-        let app;
         let maze_spec;
+        let recording;
+        let run;
+        let preferences;
+        let last_maze;
         {
             let mut state = shared_state.lock().unwrap();
             state.ctx = Some(cc.egui_ctx.clone());
             maze_spec = state.maze_spec.clone();
+            recording = state.ghost.take();
+            run = state.run;
+            preferences = state.preferences.take();
+            last_maze = state.last_maze;
         }
-        let spec = maze_spec.clone();
-        let maze = maze_from_seed_and_kind(spec.seed, spec.kind);
-        let app_state;
-        if let Some(storage) = cc.storage {
+        let maze = maze_from_spec(&maze_spec);
+        let ghost = recording.map(|recording| Ghost::new(recording, &maze));
+        let mut app_state;
+        if let Some(preferences) = preferences {
+            app_state = preferences;
+        } else if let Some(storage) = cc.storage {
             app_state = eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
         } else {
             app_state = MatahatanAppState::default();
         }
-        app = MatahatanApp {
+        // The simulation waits for it, without maze options the last maze
+        if last_maze {
+            shared_state.lock().unwrap().restart = Some(app_state.maze(&maze_spec));
+        } else {
+            app_state.size = maze_spec.size();
+        }
+        cc.egui_ctx.set_visuals(app_state.theme.visuals());
+        MatahatanApp {
             maze,
             maze_spec,
             shared_state,
             app_state,
+            ghost,
+            ghost_path: String::new(),
+            trails: Vec::new(),
+            heat: Vec::new(),
+            step_count: 10,
+            calibration: None,
+            run,
+        }
+    }
+
+    // Seed and kind for a new run, the simulation thread rebuilds the maze. The
+    // seed field takes a maze id as well.
+    fn maze_view(&mut self, ui: &mut Ui) {
+        let id = self.maze_spec.to_string();
+        ui.horizontal(|ui| {
+            ui.label("Id:");
+            let short = format!("{}…", &id[..id.len().min(24)]);
+            ui.label(RichText::new(short).monospace())
+                .on_hover_text(&id);
+            if ui.button("Copy").clicked() {
+                ui.ctx()
+                    .output_mut(|output| output.copied_text = id.clone());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Seed:");
+            ui.add(egui::TextEdit::singleline(&mut self.app_state.seed).desired_width(100.0))
+                .on_hover_text("Any string or a maze id");
+            egui::ComboBox::from_id_source("maze kind")
+                .selected_text(self.app_state.kind.name())
+                .show_ui(ui, |ui| {
+                    for kind in MazeKind::all() {
+                        ui.selectable_value(&mut self.app_state.kind, kind, kind.name());
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Random").clicked() {
+                self.app_state.seed.clear();
+                self.app_state.kind = MazeKind::random();
+                let maze_spec = self.maze_spec.with_seed(&None, self.app_state.kind);
+                self.restart(maze_spec);
+            }
+            if ui
+                .button("Restart")
+                .on_hover_text("Without seed the current maze again")
+                .clicked()
+            {
+                self.restart(self.typed_maze());
+            }
+        });
+    }
+
+    // The typed seed or maze id, the current maze if the field is empty
+    fn typed_maze(&self) -> MazeSpec {
+        let seed = self.app_state.seed.trim();
+        if seed.is_empty() {
+            return self.maze_spec.clone();
+        }
+        match seed.parse::<MazeSpec>() {
+            Ok(spec) => spec,
+            Err(_) => self
+                .maze_spec
+                .with_seed(&Some(seed.to_string()), self.app_state.kind),
+        }
+    }
+
+    fn restart(&mut self, maze_spec: MazeSpec) {
+        self.shared_state.lock().unwrap().restart = Some(maze_spec);
+    }
+
+    // Play/pause, single step, step N and speed, read by the simulation thread
+    fn control_view(&mut self, ui: &mut Ui) {
+        let mut state = self.shared_state.lock().unwrap();
+        let paced = state.config.paced;
+        let control = &mut state.control;
+        ui.horizontal(|ui| {
+            let label = if control.paused { "Play" } else { "Pause" };
+            if ui.button(label).clicked() {
+                control.paused = !control.paused;
+                control.steps = 0;
+            }
+            if ui.button("Step").clicked() {
+                control.paused = true;
+                control.steps += 1;
+            }
+            ui.add(egui::DragValue::new(&mut self.step_count).clamp_range(1..=10000));
+            if ui.button(format!("Step {}", self.step_count)).clicked() {
+                control.paused = true;
+                control.steps += self.step_count;
+            }
+            ui.separator();
+            ui.add_enabled(
+                paced,
+                egui::Slider::new(&mut control.speed, 0.1..=10.0)
+                    .logarithmic(true)
+                    .text("Speed"),
+            )
+            .on_disabled_hover_text("Runs as fast as possible in stdio-mode or at FPS 0");
+            ui.separator();
+            ui.checkbox(&mut self.app_state.show_side_panel, "Side panel");
+            let theme = self.app_state.theme;
+            egui::ComboBox::from_id_source("theme")
+                .selected_text(match theme {
+                    Theme::Dark => "Dark",
+                    Theme::Light => "Light",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.app_state.theme, Theme::Dark, "Dark");
+                    ui.selectable_value(&mut self.app_state.theme, Theme::Light, "Light");
+                });
+            if self.app_state.theme != theme {
+                ui.ctx().set_visuals(self.app_state.theme.visuals());
+            }
+        });
+    }
+
+    // Copies the new trail points, the heatmap counts them
+    fn update_trails(&mut self) {
+        let state = self.shared_state.lock().unwrap();
+        self.trails.resize(state.trails.len(), Vec::new());
+        self.heat
+            .resize((self.maze.size.0 * self.maze.size.1) as usize, 0);
+        for (index, (own, trail)) in self.trails.iter_mut().zip(&state.trails).enumerate() {
+            if own.len() > trail.len() {
+                own.clear();
+            }
+            for point in &trail[own.len()..] {
+                let cell = cell_index(&self.maze, point.position.x, point.position.y);
+                if let (0, Some(cell)) = (index, cell) {
+                    self.heat[cell] += 1;
+                }
+            }
+            own.extend_from_slice(&trail[own.len()..]);
+        }
+    }
+
+    // Live values of every controller and the mapping used by the simulation
+    fn calibration_view(&mut self, ctx: &egui::Context) {
+        let Some(gamepads) = &mut self.calibration else {
+            return;
         };
-        app
+        gamepads.poll();
+        ctx.request_repaint();
+        let mut open = true;
+        let mut state = self.shared_state.lock().unwrap();
+        let mapping = &mut state.gamepad;
+        let path = &mut self.app_state.gamepad_path;
+        egui::Window::new("Gamepad calibration")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let mut connected = false;
+                for gamepad in gamepads.all() {
+                    connected = true;
+                    let id = gamepad.id().value();
+                    ui.label(RichText::new(format!("Controller {id}")).strong());
+                    for axis in Axis::all() {
+                        ui.add(axis_bar(axis.name(), axis.value(&gamepad)));
+                    }
+                    let pressed: Vec<String> =
+                        gamepad.all_currently_pressed().map(button_name).collect();
+                    ui.label(format!("Buttons: {}", pressed.join(", ")));
+                    let (steering, throttle) = mapping.raw(&gamepad);
+                    let (shaped_steering, shaped_throttle) = mapping.shape(steering, throttle);
+                    ui.add(axis_bar("Steering", shaped_steering));
+                    ui.add(axis_bar("Acceleration", shaped_throttle));
+                    ui.separator();
+                }
+                if !connected {
+                    ui.label("No controller connected");
+                    ui.separator();
+                }
+                mapping_view(ui, mapping);
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(path).desired_width(100.0));
+                    if ui.button("Save mapping").clicked() {
+                        if let Err(error) = mapping.save(path) {
+                            log::error!("failed to save gamepad mapping: {error}");
+                        }
+                    }
+                });
+            });
+        if !open {
+            self.calibration = None;
+        }
+    }
+
+    // Last frames of the first mouse, the export has the whole run
+    fn telemetry_view(&mut self, ui: &mut Ui, max_velocity: f32) {
+        ui.horizontal(|ui| {
+            ui.label("Frames:");
+            ui.add(
+                egui::DragValue::new(&mut self.app_state.telemetry_frames).clamp_range(10..=10000),
+            );
+        });
+        let trail = self.trails.first().map(Vec::as_slice).unwrap_or_default();
+        let trail = &trail[trail.len().saturating_sub(self.app_state.telemetry_frames)..];
+        let plots = &mut self.app_state.plots;
+        ui.horizontal_wrapped(|ui| {
+            for kind in Plot::all() {
+                let mut shown = plots.contains(&kind);
+                if ui.checkbox(&mut shown, kind.name()).changed() {
+                    plots.retain(|p| *p != kind);
+                    if shown {
+                        plots.push(kind);
+                    }
+                }
+            }
+        });
+        for kind in Plot::all().into_iter().filter(|kind| plots.contains(kind)) {
+            let value = |p: &TrailPoint| kind.value(p);
+            plot(ui, kind.name(), trail, value, kind.range(max_velocity));
+        }
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.app_state.telemetry_path).desired_width(100.0),
+            );
+            if ui.button("Export CSV").clicked() {
+                let trail = self.trails.first().map(Vec::as_slice).unwrap_or_default();
+                if let Err(error) = write_telemetry(&self.app_state.telemetry_path, trail) {
+                    log::error!("failed to export telemetry: {error}");
+                }
+            }
+        });
+    }
+
+    fn ghost_view(&mut self, ui: &mut Ui, mouse: Option<&Mouse>) {
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.ghost_path).desired_width(100.0));
+            if ui.button("Load ghost").clicked() {
+                let recording = Recording::load(&self.ghost_path)
+                    .and_then(|recording| recording.check(&self.maze_spec).map(|_| recording));
+                match recording {
+                    Ok(recording) => self.ghost = Some(Ghost::new(recording, &self.maze)),
+                    Err(error) => log::error!("failed to load ghost: {error}"),
+                }
+            }
+        });
+        let Some(ghost) = &self.ghost else { return };
+        let frames = ghost.recording.frames.len();
+        debug_view_row(ui, "Ghost frames", frames as f64, FormatType::BigInt);
+        // Delta at the cell entered last by the mouse that the ghost passed too
+        let delta = mouse.and_then(|mouse| {
+            cell_deltas(&mouse.entered, &ghost.entered)
+                .max_by_key(|(_, live, _)| *live)
+                .map(|(_, _, delta)| delta)
+        });
+        if let Some(delta) = delta {
+            ui.label("Ghost delta:");
+            ui.label(
+                RichText::new(format!("{delta:+}"))
+                    .strong()
+                    .color(delta_color(delta)),
+            );
+        }
     }
 
+    // A restart brings a new maze (or the same one again) and empty trails
     fn update_maze(&mut self) {
         let maze_spec;
+        let run;
         {
             let state = self.shared_state.lock().unwrap();
             maze_spec = state.maze_spec.clone();
+            run = state.run;
         }
         if self.maze_spec != maze_spec {
-            self.maze = maze_from_seed_and_kind(maze_spec.seed, maze_spec.kind);
+            self.maze = maze_from_spec(&maze_spec);
+            self.app_state.size = maze_spec.size();
+            self.maze_spec = maze_spec;
+            self.app_state.camera.center = None;
+            // A ghost of another maze is gone, one without id stays
+            let other = |ghost: &Ghost| ghost.recording.check(&self.maze_spec).is_err();
+            if self.ghost.as_ref().is_some_and(other) {
+                self.ghost = None;
+            }
+            if let Some(ghost) = &mut self.ghost {
+                ghost.entered = ghost.recording.entered(&self.maze);
+            }
+        }
+        if self.run != run {
+            self.run = run;
+            self.trails.clear();
+            self.heat.clear();
         }
     }
 }
@@ -77,23 +597,191 @@ impl eframe::App for MatahatanApp {
     }
 
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        update_keys(ctx, &self.shared_state);
         self.update_maze();
-        let simulation;
+        self.update_trails();
+        egui::TopBottomPanel::top("controls").show(ctx, |ui| {
+            self.control_view(ui);
+        });
+        let mice;
+        let score;
+        let max_velocity;
         {
             let state = self.shared_state.lock().unwrap();
-            simulation = state.simulation.clone();
+            mice = state.mice.clone();
+            score = state.config.score.clone();
+            max_velocity = state.config.physics.max_velocity;
         }
-        egui::SidePanel::right("debug view").show(ctx, |ui| {
-            debug_view(ui, &simulation);
+        let show_side_panel = self.app_state.show_side_panel;
+        egui::SidePanel::right("debug view").show_animated(ctx, show_side_panel, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                self.maze_view(ui);
+                ui.separator();
+                if mice.len() > 1 {
+                    race_view(ui, &mice, &score);
+                    ui.separator();
+                }
+                if let Some(mouse) = mice.first() {
+                    debug_view(ui, &mouse.simulation);
+                    ui.separator();
+                    score_view(ui, &mouse.simulation, &score);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.app_state.show_trail, "Trail");
+                    ui.add(
+                        egui::DragValue::new(&mut self.app_state.trail_length)
+                            .clamp_range(0..=100000)
+                            .prefix("last ")
+                            .custom_formatter(|n, _| match n as usize {
+                                0 => "all".to_string(),
+                                n => n.to_string(),
+                            }),
+                    );
+                });
+                ui.checkbox(&mut self.app_state.show_heatmap, "Heatmap");
+                if ui.button("Gamepad calibration").clicked() && self.calibration.is_none() {
+                    self.calibration = Some(Gamepads::new());
+                }
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.app_state.camera.follow, "Follow");
+                    if ui.button("Fit").clicked() {
+                        self.app_state.camera = Camera::default();
+                    }
+                });
+                ui.separator();
+                self.ghost_view(ui, mice.first());
+                ui.separator();
+                egui::CollapsingHeader::new("Telemetry").show(ui, |ui| {
+                    self.telemetry_view(ui, max_velocity);
+                });
+                log_view(ui, &mice);
+            });
         });
+        self.calibration_view(ctx);
         egui::CentralPanel::default().show(ctx, |ui| {
-            let maze_info = maze_info(ui, &self.maze);
+            let rect = ui.available_rect_before_wrap();
+            let response = ui.allocate_rect(rect, Sense::drag());
+            let target = mice.first().map(|mouse| mouse.simulation.position);
+            self.app_state
+                .camera
+                .update(ui, &response, &self.maze, target);
+            let maze_info = self.app_state.camera.maze_info(rect, &self.maze);
+            if self.app_state.show_heatmap {
+                draw_heatmap(ui, &self.heat, &self.maze, &maze_info);
+            }
             draw_maze(ui, &self.maze, &maze_info);
-            draw_car(ui, &simulation, &maze_info);
+            if self.app_state.show_trail {
+                let length = self.app_state.trail_length;
+                for trail in &self.trails {
+                    let start = match length {
+                        0 => 0,
+                        length => trail.len().saturating_sub(length),
+                    };
+                    draw_trail(ui, &trail[start..], max_velocity, &maze_info);
+                }
+            }
+            if let (Some(ghost), Some(mouse)) = (&self.ghost, mice.first()) {
+                draw_ghost(ui, ghost, mouse, &self.maze, &maze_info);
+            }
+            for (index, mouse) in mice.iter().enumerate() {
+                let state = &mouse.simulation;
+                let color = mouse_color(index);
+                draw_car(ui, state.position, state.angle, &maze_info, color, 1.0);
+            }
         });
     }
 }
 
+// Driving keys, not while typing a seed or path
+fn update_keys(ctx: &egui::Context, shared_state: &Arc<Mutex<SharedState>>) {
+    let keys = if ctx.wants_keyboard_input() {
+        Keys::default()
+    } else {
+        ctx.input(|i| Keys {
+            up: i.key_down(Key::ArrowUp) || i.key_down(Key::W),
+            down: i.key_down(Key::ArrowDown) || i.key_down(Key::S),
+            left: i.key_down(Key::ArrowLeft) || i.key_down(Key::A),
+            right: i.key_down(Key::ArrowRight) || i.key_down(Key::D),
+        })
+    };
+    shared_state.lock().unwrap().keys = keys;
+}
+
+fn axis_bar(title: &str, value: f32) -> egui::ProgressBar {
+    egui::ProgressBar::new((value + 1.0) / 2.0)
+        .desired_width(200.0)
+        .text(format!("{title}: {value:+.3}"))
+}
+
+fn mapping_view(ui: &mut Ui, mapping: &mut GamepadMapping) {
+    egui::Grid::new("mapping").show(ui, |ui| {
+        ui.label("Steering axes");
+        axes_view(ui, &mut mapping.steering);
+        ui.checkbox(&mut mapping.invert_steering, "Invert");
+        ui.end_row();
+        ui.label("Throttle axes");
+        axes_view(ui, &mut mapping.throttle);
+        ui.checkbox(&mut mapping.invert_throttle, "Invert");
+        ui.end_row();
+        ui.label("Throttle button");
+        button_view(ui, "throttle button", &mut mapping.throttle_button);
+        ui.end_row();
+        ui.label("Brake button");
+        button_view(ui, "brake button", &mut mapping.brake_button);
+        ui.end_row();
+    });
+    curve_view(ui, "Steering", &mut mapping.steering_curve);
+    curve_view(ui, "Throttle", &mut mapping.throttle_curve);
+    if ui.button("Defaults").clicked() {
+        *mapping = GamepadMapping::default();
+    }
+}
+
+fn axes_view(ui: &mut Ui, axes: &mut Vec<Axis>) {
+    ui.horizontal(|ui| {
+        for axis in Axis::all() {
+            let mut used = axes.contains(&axis);
+            if ui.checkbox(&mut used, axis.name()).changed() {
+                axes.retain(|a| *a != axis);
+                if used {
+                    axes.push(axis);
+                }
+            }
+        }
+    });
+}
+
+fn button_view(ui: &mut Ui, id: &str, button: &mut Option<String>) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(button.as_deref().unwrap_or("none"))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(button, None, "none");
+            for name in Button::all().map(button_name) {
+                ui.selectable_value(button, Some(name.clone()), name);
+            }
+        });
+}
+
+// Sliders and the response from -1 to 1
+fn curve_view(ui: &mut Ui, title: &str, curve: &mut Curve) {
+    ui.horizontal(|ui| {
+        ui.label(title);
+        ui.add(egui::Slider::new(&mut curve.deadzone, 0.0..=0.9).text("Dead zone"));
+        ui.add(egui::Slider::new(&mut curve.exponent, 1.0..=9.0).text("Exponent"));
+    });
+    let (rect, _) = ui.allocate_exact_size(vec2(200.0, 60.0), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, Rounding::ZERO, Color32::from_gray(20));
+    let points = (0..=100)
+        .map(|i| {
+            let x = i as f32 / 50.0 - 1.0;
+            rect.lerp_inside(vec2((x + 1.0) / 2.0, (1.0 - curve.apply(x)) / 2.0))
+        })
+        .collect();
+    painter.add(Shape::line(points, Stroke::new(1.0, Color32::LIGHT_BLUE)));
+}
+
 fn debug_view(ui: &mut Ui, state: &SimulationState) {
     debug_view_row(ui, "Frame", state.frame as f64, FormatType::BigInt);
     debug_view_row(
@@ -132,6 +820,162 @@ fn debug_view(ui: &mut Ui, state: &SimulationState) {
     );
 }
 
+fn mouse_color(index: usize) -> Color32 {
+    COLORS[index % COLORS.len()]
+}
+
+// One line per mouse: name, frames and score or why it stopped
+fn race_view(ui: &mut Ui, mice: &[Mouse], score: &ScoreConfig) {
+    for (index, mouse) in mice.iter().enumerate() {
+        let state = &mouse.simulation;
+        let status = match mouse.termination {
+            Some(termination) => termination.name().to_string(),
+            None => format!("{:.0}", score.points(&state.stats, state.frame)),
+        };
+        ui.label(
+            RichText::new(&mouse.name)
+                .strong()
+                .color(mouse_color(index)),
+        );
+        ui.label(format!("{} frames, {status}", state.frame));
+    }
+}
+
+// stderr of the solver processes, newest lines at the bottom
+fn log_view(ui: &mut Ui, mice: &[Mouse]) {
+    for (index, mouse) in mice.iter().enumerate() {
+        let lines = mouse.log.lines();
+        if lines.is_empty() && mouse.exit.is_none() {
+            continue;
+        }
+        let title = match &mouse.exit {
+            Some(exit) if exit.killed => format!("Log {} (killed)", mouse.name),
+            Some(exit) => match exit.code {
+                Some(code) => format!("Log {} (exit {code})", mouse.name),
+                None => format!("Log {} (exited)", mouse.name),
+            },
+            None => format!("Log {}", mouse.name),
+        };
+        egui::CollapsingHeader::new(RichText::new(title).color(mouse_color(index)))
+            .id_source(("solver log", index))
+            .show(ui, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for line in &lines {
+                            ui.label(RichText::new(line).monospace());
+                        }
+                    });
+            });
+    }
+}
+
+fn score_view(ui: &mut Ui, state: &SimulationState, score: &ScoreConfig) {
+    let stats = &state.stats;
+    debug_view_row(
+        ui,
+        "Collisions",
+        stats.collisions as f64,
+        FormatType::BigInt,
+    );
+    debug_view_row(
+        ui,
+        "Collision frames",
+        stats.collision_frames as f64,
+        FormatType::BigInt,
+    );
+    debug_view_row(
+        ui,
+        "Longest contact",
+        stats.longest_contact as f64,
+        FormatType::BigInt,
+    );
+    debug_view_row(
+        ui,
+        "Off course",
+        stats.off_course as f64,
+        FormatType::BigInt,
+    );
+    debug_view_row(
+        ui,
+        "Score",
+        score.points(stats, state.frame) as f64,
+        FormatType::MidFloat,
+    );
+    if score.crashed(stats) {
+        ui.label(RichText::new("Disqualified").strong().color(Color32::RED));
+    }
+}
+
+// Line plot scaled to range, or to the values if there is none
+fn plot(
+    ui: &mut Ui,
+    title: &str,
+    trail: &[TrailPoint],
+    value: impl Fn(&TrailPoint) -> f32,
+    range: Option<RangeInclusive<f32>>,
+) {
+    let values: Vec<f32> = trail.iter().map(value).collect();
+    let last = values.last().copied().unwrap_or(0.0);
+    ui.label(format!("{title}: {last:+.3}"));
+    let (min, max) = match range {
+        Some(range) => (*range.start(), *range.end()),
+        None => values
+            .iter()
+            .fold((0.0f32, 0.0f32), |(min, max), v| (min.min(*v), max.max(*v))),
+    };
+    let width = ui.available_width().max(100.0);
+    let (rect, _) = ui.allocate_exact_size(vec2(width, 40.0), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, Rounding::ZERO, Color32::from_gray(20));
+    let span = (max - min).max(f32::EPSILON);
+    let y = |v: f32| rect.bottom() - (v - min) / span * rect.height();
+    if min < 0.0 && max > 0.0 {
+        let zero = y(0.0);
+        let stroke = Stroke::new(1.0, Color32::DARK_GRAY);
+        painter.line_segment(
+            [Pos2::new(rect.left(), zero), Pos2::new(rect.right(), zero)],
+            stroke,
+        );
+    }
+    if values.len() < 2 {
+        return;
+    }
+    let dx = rect.width() / (values.len() - 1) as f32;
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| Pos2::new(rect.left() + i as f32 * dx, y(*v)))
+        .collect();
+    painter.add(Shape::line(points, Stroke::new(1.0, Color32::LIGHT_BLUE)));
+}
+
+fn write_telemetry(path: &str, trail: &[TrailPoint]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "frame,x,y,angle,velocity,true_velocity,steering,acceleration,collision"
+    )?;
+    for p in trail {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{}",
+            p.frame,
+            p.position.x,
+            p.position.y,
+            p.angle,
+            p.velocity,
+            p.true_velocity,
+            p.steering,
+            p.acceleration,
+            p.collision
+        )?;
+    }
+    writer.flush()
+}
+
 fn debug_view_row(ui: &mut Ui, title: &str, value: f64, format_type: FormatType) {
     ui.label(format!("{title}:"));
     let display = match format_type {
@@ -142,23 +986,8 @@ fn debug_view_row(ui: &mut Ui, title: &str, value: f64, format_type: FormatType)
     ui.label(RichText::new(display).strong());
 }
 
-fn maze_info(ui: &mut Ui, maze: &Maze) -> MazeInfo {
-    let size = ui.available_size();
-    let top_left = Pos2 { x: 20.0, y: 15.0 };
-    let bottom_right = Pos2 {
-        x: size.x,
-        y: size.y,
-    };
-    let border = Rect::from_two_pos(top_left, bottom_right);
-    let y = maze.size.0 as f32;
-    let x = maze.size.1 as f32;
-    let square = vec2(border.width() / x as f32, border.height() / y as f32);
-    MazeInfo {
-        border,
-        square,
-        x,
-        y,
-    }
+fn maze_size(maze: &Maze) -> Vec2 {
+    vec2(maze.size.0 as f32, maze.size.1 as f32)
 }
 
 fn rotate(v: egui::Vec2, angle_rad: f32) -> egui::Vec2 {
@@ -171,20 +1000,74 @@ fn rotate(v: egui::Vec2, angle_rad: f32) -> egui::Vec2 {
     )
 }
 
-fn draw_car(ui: &mut Ui, simulation: &SimulationState, maze_info: &MazeInfo) {
-    let stroke = Stroke::new(1.0, Color32::WHITE);
-    let pos = &simulation.position;
+// Cells both passed, with the frame the mouse entered and frames it is behind
+fn cell_deltas<'a>(
+    live: &'a [Option<i64>],
+    ghost: &'a [Option<i64>],
+) -> impl Iterator<Item = (usize, i64, i64)> + 'a {
+    live.iter()
+        .zip(ghost)
+        .enumerate()
+        .filter_map(|(cell, (live, ghost))| Some((cell, (*live)?, (*live)? - (*ghost)?)))
+}
+
+fn delta_color(delta: i64) -> Color32 {
+    match delta.signum() {
+        1 => Color32::LIGHT_RED,
+        -1 => Color32::LIGHT_GREEN,
+        _ => Color32::GRAY,
+    }
+}
+
+fn draw_ghost(ui: &mut Ui, ghost: &Ghost, mouse: &Mouse, maze: &Maze, maze_info: &MazeInfo) {
+    let font = FontId::proportional(maze_info.square.y * 0.35);
+    let width = maze.size.0 as usize;
+    for (cell, _, delta) in cell_deltas(&mouse.entered, &ghost.entered) {
+        let x = ((cell % width) as f32 + 0.5) / maze_info.x;
+        let y = ((cell / width) as f32 + 0.5) / maze_info.y;
+        let center = maze_info.border.lerp_inside(vec2(x, y));
+        let text = format!("{delta:+}");
+        ui.painter().text(
+            center,
+            Align2::CENTER_CENTER,
+            text,
+            font.clone(),
+            delta_color(delta),
+        );
+    }
+    if let Some(output) = ghost.recording.at(mouse.simulation.frame) {
+        let position = vec2(output.x, output.y);
+        draw_car(
+            ui,
+            position,
+            output.angle,
+            maze_info,
+            Color32::LIGHT_GRAY,
+            0.4,
+        );
+    }
+}
+
+fn draw_car(
+    ui: &mut Ui,
+    pos: Vec2,
+    angle: f32,
+    maze_info: &MazeInfo,
+    color: Color32,
+    opacity: f32,
+) {
+    let stroke = Stroke::new(1.0, Color32::WHITE.gamma_multiply(opacity));
     let square = maze_info.square;
     let v1 = vec2(0.6 * square.x, 0.0);
     let v2 = vec2(0.0, 0.15 * square.y);
     let v3 = vec2(0.0, -0.15 * square.y);
-    let mut vec = vec![v1, v2, v3];
-    for i in 0..vec.len() {
-        vec[i] = rotate(vec[i], simulation.angle);
+    let mut vec = [v1, v2, v3];
+    for v in &mut vec {
+        *v = rotate(*v, angle);
     }
     let mut shape = Shape::convex_polygon(
         vec![vec[0].to_pos2(), vec[1].to_pos2(), vec[2].to_pos2()],
-        Color32::LIGHT_YELLOW,
+        color.gamma_multiply(opacity),
         stroke,
     );
     let x = pos.x / maze_info.x;
@@ -194,36 +1077,90 @@ fn draw_car(ui: &mut Ui, simulation: &SimulationState, maze_info: &MazeInfo) {
     ui.painter().add(shape);
 }
 
+fn to_screen(maze_info: &MazeInfo, position: Vec2) -> Pos2 {
+    let relative = vec2(position.x / maze_info.x, position.y / maze_info.y);
+    maze_info.border.lerp_inside(relative)
+}
+
+// Blue when slow, over yellow to red at max velocity
+fn velocity_color(velocity: f32, max_velocity: f32) -> Color32 {
+    let t = (velocity / max_velocity).clamp(0.0, 1.0);
+    let channel = |a: f32, b: f32, t: f32| (a + (b - a) * t) as u8;
+    if t < 0.5 {
+        let t = t * 2.0;
+        Color32::from_rgb(
+            channel(60.0, 255.0, t),
+            channel(120.0, 220.0, t),
+            channel(255.0, 0.0, t),
+        )
+    } else {
+        let t = (t - 0.5) * 2.0;
+        Color32::from_rgb(255, channel(220.0, 40.0, t), 0)
+    }
+}
+
+// Collision points are marked where a wall contact starts
+fn draw_trail(ui: &mut Ui, trail: &[TrailPoint], max_velocity: f32, maze_info: &MazeInfo) {
+    let painter = ui.painter();
+    for pair in trail.windows(2) {
+        let color = velocity_color(pair[1].velocity, max_velocity);
+        let a = to_screen(maze_info, pair[0].position);
+        let b = to_screen(maze_info, pair[1].position);
+        painter.line_segment([a, b], Stroke::new(2.0, color));
+        if pair[1].collision && !pair[0].collision {
+            painter.circle_stroke(b, 3.0, Stroke::new(1.5, Color32::RED));
+        }
+    }
+}
+
+// Cells the mouse spent more time in are more opaque
+fn draw_heatmap(ui: &mut Ui, heat: &[i64], maze: &Maze, maze_info: &MazeInfo) {
+    let max = heat.iter().copied().max().unwrap_or(0);
+    if max == 0 {
+        return;
+    }
+    let width = maze.size.0 as usize;
+    for (cell, frames) in heat.iter().enumerate().filter(|(_, frames)| **frames > 0) {
+        let x = (cell % width) as f32 + 0.5;
+        let y = (cell / width) as f32 + 0.5;
+        let rect = Rect::from_center_size(to_screen(maze_info, vec2(x, y)), maze_info.square);
+        let alpha = 30.0 + 170.0 * *frames as f32 / max as f32;
+        let color = Color32::from_rgba_unmultiplied(255, 80, 0, alpha as u8);
+        ui.painter()
+            .add(Shape::rect_filled(rect, Rounding::ZERO, color));
+    }
+}
+
 fn draw_maze(ui: &mut Ui, maze: &Maze, maze_info: &MazeInfo) {
-    let stroke = Stroke::new(1.0, Color32::WHITE);
-    let shape = Shape::rect_stroke(maze_info.border, Rounding::ZERO, stroke);
-    ui.painter().add(shape);
+    // Thicker walls when zoomed in
+    let width = (maze_info.square.x * 0.04).clamp(1.0, 4.0);
+    let stroke = Stroke::new(width, ui.visuals().strong_text_color());
     let gx = (0.5 + maze.goal.x as f32) / maze_info.x;
     let gy = (0.5 + maze.goal.y as f32) / maze_info.y;
     let center = maze_info.border.lerp_inside(vec2(gx, gy));
     let start = Rect::from_center_size(center, maze_info.square);
     let shape = Shape::rect_filled(start, Rounding::ZERO, Color32::DARK_GREEN);
     ui.painter().add(shape);
-    for ix in 0..maze_info.x as i32 {
-        for iy in 0..maze_info.y as i32 {
-            if let Some(field) = maze.get_field(&(ix, iy).into()) {
-                let x = (ix as f32 + 0.5) / maze_info.x;
-                let y = (iy as f32 + 0.5) / maze_info.y;
-                let center = maze_info.border.lerp_inside(vec2(x, y));
-                let square = Rect::from_center_size(center, maze_info.square);
-                draw_field(ui, stroke, &square, &field);
-            }
-        }
+    // Same walls as the collision world, so open rooms are drawn as they
+    // collide and the outer wall is part of the list.
+    for wall in maze.walls() {
+        draw_wall(ui, stroke, &wall, maze_info);
     }
 }
 
-fn draw_field(ui: &mut Ui, stroke: Stroke, rect: &Rect, field: &Field) {
-    if !field.has_passage(&Direction::West) {
-        draw_line(ui, stroke, rect.left_top(), rect.left_bottom());
-    }
-    if !field.has_passage(&Direction::North) {
-        draw_line(ui, stroke, rect.left_top(), rect.right_top());
-    }
+fn draw_wall(ui: &mut Ui, stroke: Stroke, wall: &Wall, maze_info: &MazeInfo) {
+    let (ex, ey) = if wall.vertical { (0, 1) } else { (1, 0) };
+    let a = vec2(wall.x as f32 / maze_info.x, wall.y as f32 / maze_info.y);
+    let b = vec2(
+        (wall.x + ex) as f32 / maze_info.x,
+        (wall.y + ey) as f32 / maze_info.y,
+    );
+    draw_line(
+        ui,
+        stroke,
+        maze_info.border.lerp_inside(a),
+        maze_info.border.lerp_inside(b),
+    );
 }
 
 fn draw_line(ui: &mut Ui, stroke: Stroke, a: Pos2, b: Pos2) {
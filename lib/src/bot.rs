@@ -0,0 +1,178 @@
+use super::analysis::{corner_velocity, shortest_path};
+use super::maze::{Coordinates, Maze};
+use super::stdio::{Input, Output};
+use super::{PhysicsProfile, Solver};
+use egui::{vec2, Vec2};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+const PI: f32 = std::f32::consts::PI;
+// Slow down to this velocity if we are facing the wrong way
+const CRAWL_VELOCITY: f32 = 0.02;
+
+// The default tuning drives from cell center to cell center, a lookahead
+// steers towards a point further along the path and cuts corners.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BotTuning {
+    pub lookahead: f32,
+    pub turn_distance: f32,
+    pub corner_factor: f32,
+    pub steering_gain: f32,
+}
+
+impl Default for BotTuning {
+    fn default() -> Self {
+        BotTuning {
+            lookahead: 0.0,
+            turn_distance: 0.5,
+            corner_factor: 1.0,
+            steering_gain: 0.5,
+        }
+    }
+}
+
+// Baseline solver: follows the shortest path from cell center to cell center
+// and brakes before corners, so it can take them at corner velocity. It only
+// uses what a stdio-solver gets (maze and outputs), the physics profile is
+// used to know how hard it can brake.
+pub struct ReferenceBot {
+    physics: PhysicsProfile,
+    tuning: BotTuning,
+    path: Vec<Coordinates>,
+    index: usize,
+    position: Vec2,
+    angle: f32,
+    velocity: f32,
+}
+
+impl ReferenceBot {
+    pub fn new(physics: &PhysicsProfile) -> Self {
+        ReferenceBot::with_tuning(physics, &BotTuning::default())
+    }
+
+    pub fn with_tuning(physics: &PhysicsProfile, tuning: &BotTuning) -> Self {
+        ReferenceBot {
+            physics: physics.clone(),
+            tuning: tuning.clone(),
+            path: Vec::new(),
+            index: 0,
+            position: vec2(0.5, 0.5),
+            angle: 0.0,
+            velocity: 0.0,
+        }
+    }
+
+    fn center(&self, index: usize) -> Vec2 {
+        let cell = self.path[index.min(self.path.len() - 1)];
+        vec2(cell.x as f32 + 0.5, cell.y as f32 + 0.5)
+    }
+
+    // Point on the path lookahead cells after the next cell center
+    fn target(&self) -> Vec2 {
+        let mut point = self.center(self.index + 1);
+        let mut remaining = self.tuning.lookahead;
+        let mut index = self.index + 1;
+        while remaining > 0.0 && index + 1 < self.path.len() {
+            let next = self.center(index + 1);
+            let segment = next - point;
+            let length = segment.length();
+            if length >= remaining {
+                return point + segment * (remaining / length);
+            }
+            remaining -= length;
+            point = next;
+            index += 1;
+        }
+        point
+    }
+
+    // Index of the next cell where the path turns (or the last cell)
+    fn next_corner(&self) -> usize {
+        let direction = |i: usize| {
+            (
+                self.path[i + 1].x - self.path[i].x,
+                self.path[i + 1].y - self.path[i].y,
+            )
+        };
+        let mut corner = self.index + 1;
+        while corner + 1 < self.path.len() && direction(corner) == direction(self.index) {
+            corner += 1;
+        }
+        corner
+    }
+
+    fn target_velocity(&self, heading_error: f32) -> f32 {
+        let physics = &self.physics;
+        if heading_error.abs() > PI / 4.0 {
+            return CRAWL_VELOCITY;
+        }
+        let corner = self.next_corner();
+        if corner == self.path.len() - 1 {
+            return physics.max_velocity;
+        }
+        // Reach corner velocity turn_distance before the corner center
+        let distance =
+            ((self.center(corner) - self.position).length() - self.tuning.turn_distance).max(0.0);
+        let brake = physics.acceleration_scaler * physics.brake_scaler;
+        let corner_velocity = corner_velocity(physics) * self.tuning.corner_factor;
+        (corner_velocity * corner_velocity + 2.0 * brake * distance)
+            .sqrt()
+            .min(physics.max_velocity)
+    }
+}
+
+impl Solver for ReferenceBot {
+    fn maze(&mut self, maze: &Maze) {
+        self.path = shortest_path(maze).unwrap_or_default();
+        self.index = 0;
+        // A restarted run starts at the entrance again
+        self.position = vec2(0.5, 0.5);
+        self.angle = 0.0;
+        self.velocity = 0.0;
+    }
+
+    fn input(&mut self, _deadline: Option<Instant>) -> Option<Input> {
+        if self.index + 1 >= self.path.len() {
+            return Some(Input {
+                acceleration: 0.0,
+                steering: 0.0,
+            });
+        }
+        let physics = &self.physics;
+        let target = self.target() - self.position;
+        let mut heading_error = target.angle() - self.angle;
+        while heading_error > PI {
+            heading_error -= 2.0 * PI;
+        }
+        while heading_error < -PI {
+            heading_error += 2.0 * PI;
+        }
+        // With a gain of 0.5 the error would be gone in two frames
+        let vel_scale = (self.velocity * physics.turn_scaler).max(1.0);
+        let steering =
+            heading_error * vel_scale / physics.steering_scaler * self.tuning.steering_gain;
+        let difference = self.target_velocity(heading_error) - self.velocity;
+        let acceleration = if difference > 0.0 {
+            difference / physics.acceleration_scaler
+        } else {
+            difference / (physics.acceleration_scaler * physics.brake_scaler)
+        };
+        Some(Input {
+            acceleration: acceleration.clamp(-1.0, 1.0),
+            steering: steering.clamp(-1.0, 1.0),
+        })
+    }
+
+    fn output(&mut self, output: &Output) {
+        let position = vec2(output.x, output.y);
+        self.velocity = (position - self.position).length();
+        self.position = position;
+        self.angle = output.angle;
+        let cell: Coordinates = (output.x.floor() as i32, output.y.floor() as i32).into();
+        // Only look ahead a little, so crossing our own path does not skip it
+        let end = (self.index + 3).min(self.path.len());
+        if let Some(offset) = self.path[self.index..end].iter().position(|c| *c == cell) {
+            self.index += offset;
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use super::app::MatahatanAppState;
+use super::{
+    Bot, Config, GamepadMapping, Limits, MazeKind, MazeSpec, PhysicsProfile, Racer, ScoreConfig,
+    MAX_MAZE_SIZE, MAX_ROOMS, MAX_STAIRS, MAZE_SIZE,
+};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+// How the first mouse is driven, together with solver and bot only one may be
+// given
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    Stdio,
+    Stick,
+    Keyboard,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct MazeSection {
+    // Empty or missing is a random maze
+    pub seed: Option<String>,
+    pub kind: Option<MazeKind>,
+    // Width and height in cells
+    pub size: Option<(i32, i32)>,
+    pub braid: Option<f32>,
+    pub rooms: Option<u32>,
+    pub stairs: Option<u32>,
+    // Maze id as printed by simulate, instead of all of the above
+    pub id: Option<String>,
+}
+
+impl MazeSection {
+    // Which of the keys that id replaces are given
+    fn features(&self) -> [bool; 6] {
+        [
+            self.seed.is_some(),
+            self.kind.is_some(),
+            self.size.is_some(),
+            self.braid.is_some(),
+            self.rooms.is_some(),
+            self.stairs.is_some(),
+        ]
+    }
+}
+
+// Like Limits, times in seconds
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsSection {
+    pub max_frames: Option<i64>,
+    pub max_collisions: Option<i64>,
+    pub max_collision_frames: Option<i64>,
+    pub timeout: Option<f32>,
+    pub frame_timeout: Option<f32>,
+}
+
+// Everything simulate takes as TOML file, missing values are the defaults of
+// the command line. Options given on the command line replace the file's.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub gui: Option<bool>,
+    pub fps: Option<f32>,
+    pub transport: Option<Transport>,
+    pub solver: Option<String>,
+    pub bot: Option<Bot>,
+    // Like --racer
+    pub racers: Option<Vec<String>>,
+    pub mouse_collisions: Option<bool>,
+    pub ratio: Option<bool>,
+    pub paused: Option<bool>,
+    pub record: Option<String>,
+    pub ghost: Option<String>,
+    pub maze: MazeSection,
+    pub physics: Option<PhysicsProfile>,
+    pub limits: LimitsSection,
+    pub score: Option<ScoreConfig>,
+    pub gamepad: Option<GamepadMapping>,
+    // GUI preferences, replace the stored ones
+    pub preferences: Option<MatahatanAppState>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        toml::from_str(text)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+    }
+
+    pub fn config(&self) -> io::Result<Config> {
+        self.check().map_err(io::Error::other)?;
+        let gui = self.gui.unwrap_or(true);
+        let mut racers = Vec::new();
+        for spec in self.racers.iter().flatten() {
+            match Racer::parse(spec) {
+                Some(racer) => racers.push(racer),
+                None => return Err(io::Error::other(format!("unknown racer '{spec}'"))),
+            }
+        }
+        let maze = &self.maze;
+        let maze_id = match &maze.id {
+            Some(id) => Some(id.parse::<MazeSpec>().map_err(io::Error::other)?),
+            None => None,
+        };
+        let limits = &self.limits;
+        let seconds = |secs: Option<f32>| secs.map(Duration::from_secs_f32);
+        Ok(Config {
+            gui,
+            stdio: self.transport == Some(Transport::Stdio),
+            stick: self.transport == Some(Transport::Stick),
+            keyboard: self.transport == Some(Transport::Keyboard),
+            solver: self.solver.clone(),
+            gamepad: self.gamepad.clone().unwrap_or_default(),
+            framerate: if gui { self.fps.unwrap_or(25.0) } else { 0.0 },
+            seed: maze.seed.clone().filter(|seed| !seed.is_empty()),
+            kind: maze.kind.unwrap_or(MazeKind::Backtracking),
+            maze_id,
+            size: maze.size.unwrap_or(MAZE_SIZE),
+            braid: maze.braid.unwrap_or(0.0),
+            rooms: maze.rooms.unwrap_or(0),
+            stairs: maze.stairs.unwrap_or(0),
+            physics: self.physics.clone().unwrap_or_default(),
+            bot: self.bot,
+            ratio: self.ratio.unwrap_or(false),
+            limits: Limits {
+                max_frames: limits.max_frames,
+                max_collisions: limits.max_collisions,
+                max_collision_frames: limits.max_collision_frames,
+                timeout: seconds(limits.timeout),
+                frame_timeout: seconds(limits.frame_timeout),
+            },
+            score: self.score.clone().unwrap_or_default(),
+            racers,
+            mouse_collisions: self.mouse_collisions.unwrap_or(false),
+            record: self.record.clone(),
+            ghost: self.ghost.clone(),
+            paused: gui && self.paused.unwrap_or(false),
+            preferences: self.preferences.clone(),
+            maze_given: maze.id.is_some() || maze.features().contains(&true),
+        })
+    }
+
+    // The same rules as for the command line
+    fn check(&self) -> Result<(), String> {
+        let drivers = [
+            self.transport.is_some(),
+            self.solver.is_some(),
+            self.bot.is_some(),
+        ];
+        if drivers.iter().filter(|given| **given).count() > 1 {
+            return Err("only one of transport, solver and bot can be given".to_string());
+        }
+        let gui = self.gui.unwrap_or(true);
+        if !gui && self.transport == Some(Transport::Keyboard) {
+            return Err("the keyboard transport needs the GUI".to_string());
+        }
+        if !gui && self.paused == Some(true) {
+            return Err("paused needs the GUI".to_string());
+        }
+        if self.fps.is_some_and(|fps| !(fps >= 0.0 && fps.is_finite())) {
+            return Err("fps must be at least 0".to_string());
+        }
+        let maze = &self.maze;
+        if maze.id.is_some() && maze.features().contains(&true) {
+            return Err("maze.id cannot be combined with the other keys of maze".to_string());
+        }
+        if let Some((width, height)) = self.maze.size {
+            let range = 2..=MAX_MAZE_SIZE;
+            if !range.contains(&width) || !range.contains(&height) {
+                return Err(format!(
+                    "maze.size must be from [2, 2] to [{MAX_MAZE_SIZE}, {MAX_MAZE_SIZE}]"
+                ));
+            }
+        }
+        if self
+            .maze
+            .braid
+            .is_some_and(|braid| !(0.0..=1.0).contains(&braid))
+        {
+            return Err("maze.braid must be from 0.0 to 1.0".to_string());
+        }
+        if maze.rooms.is_some_and(|rooms| rooms > MAX_ROOMS)
+            || maze.stairs.is_some_and(|stairs| stairs > MAX_STAIRS)
+        {
+            return Err(format!(
+                "maze.rooms and maze.stairs must be at most {MAX_ROOMS} and {MAX_STAIRS}"
+            ));
+        }
+        let seconds = [self.limits.timeout, self.limits.frame_timeout];
+        if seconds
+            .iter()
+            .flatten()
+            .any(|secs| !(*secs > 0.0 && secs.is_finite()))
+        {
+            return Err(
+                "limits.timeout and limits.frame_timeout must be greater than 0".to_string(),
+            );
+        }
+        if let Some(gamepad) = &self.gamepad {
+            gamepad
+                .check()
+                .map_err(|error| format!("gamepad.{error}"))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use super::process::ProcessSolver;
+use super::{
+    maze_from_spec, run_headless, Limits, MazeKind, MazeSpec, PhysicsProfile, RunResult,
+    ScoreConfig, SimulationState, Termination,
+};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+#[derive(Clone)]
+pub struct EvalConfig {
+    pub solver: String,
+    pub seeds: Vec<String>,
+    pub kinds: Vec<MazeKind>,
+    pub size: (i32, i32),
+    pub braid: f32,
+    pub rooms: u32,
+    pub stairs: u32,
+    pub physics: PhysicsProfile,
+    pub jobs: usize,
+    pub limits: Limits,
+    pub score: ScoreConfig,
+    pub ratio: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EvalRun {
+    pub seed: String,
+    pub kind: &'static str,
+    #[serde(flatten)]
+    pub result: RunResult,
+}
+
+// Means are taken over the runs that reached the goal, the mean score over
+// the runs that were not disqualified
+#[derive(Serialize, Debug, Clone)]
+pub struct EvalSummary {
+    pub runs: usize,
+    pub goals: usize,
+    pub failures: usize,
+    pub disqualified: usize,
+    pub collisions: i64,
+    pub collision_frames: i64,
+    pub off_course: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_frames: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_score: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_ratio: Option<f32>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EvalReport {
+    pub summary: EvalSummary,
+    pub runs: Vec<EvalRun>,
+}
+
+// Runs the solver on every seed/kind combination, each job in its own solver
+// process, jobs at a time. The runs are reported in seed/kind order.
+pub fn evaluate(config: &EvalConfig) -> EvalReport {
+    let queue: VecDeque<(usize, String, MazeKind)> = config
+        .seeds
+        .iter()
+        .flat_map(|seed| config.kinds.iter().map(move |kind| (seed.clone(), *kind)))
+        .enumerate()
+        .map(|(index, (seed, kind))| (index, seed, kind))
+        .collect();
+    let count = queue.len();
+    let queue = Arc::new(Mutex::new(queue));
+    let (sender, receiver) = mpsc::channel();
+    let mut handles = Vec::new();
+    for _ in 0..config.jobs.clamp(1, count.max(1)) {
+        let queue = queue.clone();
+        let sender = sender.clone();
+        let config = config.clone();
+        handles.push(thread::spawn(move || loop {
+            let job = queue.lock().unwrap().pop_front();
+            let Some((index, seed, kind)) = job else {
+                break;
+            };
+            let run = evaluate_maze(&config, seed, kind);
+            if sender.send((index, run)).is_err() {
+                break;
+            }
+        }));
+    }
+    drop(sender);
+    let mut runs: Vec<(usize, EvalRun)> = receiver.iter().collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    runs.sort_by_key(|(index, _)| *index);
+    let runs: Vec<EvalRun> = runs.into_iter().map(|(_, run)| run).collect();
+    EvalReport {
+        summary: summarize(&runs),
+        runs,
+    }
+}
+
+fn evaluate_maze(config: &EvalConfig, seed: String, kind: MazeKind) -> EvalRun {
+    let spec = MazeSpec::from_string(&seed, kind)
+        .with_size(config.size)
+        .with_braid(config.braid)
+        .with_rooms(config.rooms)
+        .with_stairs(config.stairs);
+    let maze = maze_from_spec(&spec);
+    let mut result = match ProcessSolver::spawn(&config.solver) {
+        Ok(mut solver) => run_headless(&maze, &config.physics, &mut solver, &config.limits),
+        Err(error) => {
+            eprintln!("failed to start solver: {error}");
+            RunResult::new(&SimulationState::new(), Termination::SpawnFailed)
+        }
+    };
+    result = result.with_score(&config.score);
+    if config.ratio && result.goal {
+        result = result.with_bound(&maze, &config.physics);
+    }
+    EvalRun {
+        seed,
+        kind: kind.name(),
+        result,
+    }
+}
+
+fn summarize(runs: &[EvalRun]) -> EvalSummary {
+    let goals: Vec<&RunResult> = runs
+        .iter()
+        .map(|run| &run.result)
+        .filter(|result| result.goal)
+        .collect();
+    let scores: Vec<f32> = runs
+        .iter()
+        .filter_map(|run| run.result.score.as_ref())
+        .filter(|score| !score.disqualified)
+        .map(|score| score.points)
+        .collect();
+    let mean = |values: Vec<f32>| {
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.iter().sum::<f32>() / values.len() as f32)
+        }
+    };
+    EvalSummary {
+        runs: runs.len(),
+        goals: goals.len(),
+        failures: runs.len() - goals.len(),
+        disqualified: runs.len() - scores.len(),
+        collisions: runs.iter().map(|run| run.result.stats.collisions).sum(),
+        collision_frames: runs
+            .iter()
+            .map(|run| run.result.stats.collision_frames)
+            .sum(),
+        off_course: runs.iter().map(|run| run.result.stats.off_course).sum(),
+        mean_frames: mean(goals.iter().map(|result| result.frames as f32).collect()),
+        mean_score: mean(scores),
+        mean_ratio: mean(goals.iter().filter_map(|result| result.ratio).collect()),
+    }
+}
+
+impl EvalSummary {
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_json_line(writer, self)
+    }
+}
+
+impl EvalReport {
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_json_line(writer, self)
+    }
+
+    // One line per maze, the summary is left to the caller
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "seed,kind,termination,frames,goal,collisions,collision_frames,longest_contact,\
+             off_course,score,disqualified,bound,ratio,exit_code,killed"
+        )?;
+        for run in &self.runs {
+            let result = &run.result;
+            let stats = &result.stats;
+            let exit = result.exit.as_ref();
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                csv_field(&run.seed),
+                run.kind,
+                result.termination.name(),
+                result.frames,
+                result.goal,
+                stats.collisions,
+                stats.collision_frames,
+                stats.longest_contact,
+                stats.off_course,
+                result
+                    .score
+                    .as_ref()
+                    .map(|s| s.points.to_string())
+                    .unwrap_or_default(),
+                result.score.as_ref().is_some_and(|s| s.disqualified),
+                result.bound.map(|b| b.to_string()).unwrap_or_default(),
+                result.ratio.map(|r| r.to_string()).unwrap_or_default(),
+                exit.and_then(|e| e.code)
+                    .map(|c| c.to_string())
+                    .unwrap_or_default(),
+                exit.is_some_and(|e| e.killed),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn write_json_line<W: Write, T: Serialize>(writer: &mut W, value: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, value)?;
+    writeln!(writer)
+}
+
+// Seeds are any string, quote them if needed
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
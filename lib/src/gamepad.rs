@@ -0,0 +1,193 @@
+use gamepads::{Button, Gamepad};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Axis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+}
+
+impl Axis {
+    pub fn all() -> [Axis; 4] {
+        [Axis::LeftX, Axis::LeftY, Axis::RightX, Axis::RightY]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Axis::LeftX => "left_x",
+            Axis::LeftY => "left_y",
+            Axis::RightX => "right_x",
+            Axis::RightY => "right_y",
+        }
+    }
+
+    // Right and up are positive
+    pub fn value(&self, gamepad: &Gamepad) -> f32 {
+        match self {
+            Axis::LeftX => gamepad.left_stick_x(),
+            Axis::LeftY => gamepad.left_stick_y(),
+            Axis::RightX => gamepad.right_stick_x(),
+            Axis::RightY => gamepad.right_stick_y(),
+        }
+    }
+}
+
+// Zero inside the dead zone, outside the value to the power of exponent,
+// keeping the sign. A high exponent gives fine control around the middle.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Curve {
+    pub deadzone: f32,
+    pub exponent: f32,
+}
+
+impl Default for Curve {
+    fn default() -> Self {
+        Curve {
+            deadzone: 0.0,
+            exponent: 1.0,
+        }
+    }
+}
+
+impl Curve {
+    pub fn apply(&self, value: f32) -> f32 {
+        if value.abs() < self.deadzone {
+            0.0
+        } else {
+            value.signum() * value.abs().powf(self.exponent)
+        }
+    }
+
+    fn check(&self, name: &str) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.deadzone) {
+            return Err(format!("{name}.deadzone must be from 0.0 to 1.0"));
+        }
+        if !(self.exponent > 0.0 && self.exponent.is_finite()) {
+            return Err(format!("{name}.exponent must be greater than 0"));
+        }
+        Ok(())
+    }
+}
+
+// How human input becomes steering and acceleration, the curves are also
+// used for the keyboard. Buttons are named like gamepads::Button
+// (FrontRightLower is R2/RT), the triggers are buttons in that crate.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamepadMapping {
+    // The axes are summed, by default both sticks work
+    pub steering: Vec<Axis>,
+    pub throttle: Vec<Axis>,
+    pub invert_steering: bool,
+    pub invert_throttle: bool,
+    // Full throttle or brake while pressed, brake wins
+    pub throttle_button: Option<String>,
+    pub brake_button: Option<String>,
+    pub steering_curve: Curve,
+    pub throttle_curve: Curve,
+}
+
+impl Default for GamepadMapping {
+    fn default() -> Self {
+        GamepadMapping {
+            steering: vec![Axis::LeftX, Axis::RightX],
+            throttle: vec![Axis::LeftY, Axis::RightY],
+            invert_steering: false,
+            invert_throttle: false,
+            throttle_button: Some("FrontRightLower".to_string()),
+            brake_button: Some("FrontLeftLower".to_string()),
+            steering_curve: Curve {
+                deadzone: 0.2,
+                exponent: 7.0,
+            },
+            throttle_curve: Curve {
+                deadzone: 0.0,
+                exponent: 3.0,
+            },
+        }
+    }
+}
+
+impl GamepadMapping {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mapping: Self = serde_json::from_reader(BufReader::new(file))?;
+        mapping
+            .check()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(mapping)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)
+    }
+
+    // Button names and curve ranges, serde only checks the types
+    pub fn check(&self) -> Result<(), String> {
+        for (field, name) in [
+            ("throttle_button", &self.throttle_button),
+            ("brake_button", &self.brake_button),
+        ] {
+            if let Some(name) = name.as_deref().filter(|name| button(name).is_none()) {
+                return Err(format!("{field} '{name}' is not a gamepad button"));
+            }
+        }
+        self.steering_curve.check("steering_curve")?;
+        self.throttle_curve.check("throttle_curve")
+    }
+
+    // Steering and throttle before the curves
+    pub fn raw(&self, gamepad: &Gamepad) -> (f32, f32) {
+        let sum = |axes: &[Axis], invert: bool| {
+            let value: f32 = axes.iter().map(|axis| axis.value(gamepad)).sum();
+            let value = value.clamp(-1.0, 1.0);
+            if invert {
+                -value
+            } else {
+                value
+            }
+        };
+        let steering = sum(&self.steering, self.invert_steering);
+        let mut throttle = sum(&self.throttle, self.invert_throttle);
+        let pressed = |name: &Option<String>| {
+            name.as_deref()
+                .and_then(button)
+                .is_some_and(|button| gamepad.is_currently_pressed(button))
+        };
+        if pressed(&self.throttle_button) {
+            throttle = 1.0;
+        }
+        if pressed(&self.brake_button) {
+            throttle = -1.0;
+        }
+        (steering, throttle)
+    }
+
+    pub fn shape(&self, steering: f32, throttle: f32) -> (f32, f32) {
+        (
+            self.steering_curve.apply(steering),
+            self.throttle_curve.apply(throttle),
+        )
+    }
+
+    pub fn input(&self, gamepad: &Gamepad) -> (f32, f32) {
+        let (steering, throttle) = self.raw(gamepad);
+        self.shape(steering, throttle)
+    }
+}
+
+pub fn button_name(button: Button) -> String {
+    format!("{button:?}")
+}
+
+pub fn button(name: &str) -> Option<Button> {
+    Button::all().find(|button| button_name(*button) == name)
+}
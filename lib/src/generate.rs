@@ -0,0 +1,74 @@
+use super::stdio::{maze_json, MazeJson};
+use super::{maze_from_spec, MazeSpec};
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MazeFormat {
+    // The header a solver gets plus the hash of the spec
+    Json,
+    Ascii,
+    // Micromouse wall bytes
+    Maz,
+}
+
+impl MazeFormat {
+    pub fn all() -> [MazeFormat; 3] {
+        [MazeFormat::Json, MazeFormat::Ascii, MazeFormat::Maz]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MazeFormat::Json => "json",
+            MazeFormat::Ascii => "ascii",
+            MazeFormat::Maz => "maz",
+        }
+    }
+}
+
+impl FromStr for MazeFormat {
+    type Err = String;
+
+    fn from_str(format_str: &str) -> Result<Self, Self::Err> {
+        MazeFormat::all()
+            .into_iter()
+            .find(|format| format.name() == format_str)
+            .ok_or_else(|| format!("unknown maze format '{format_str}' (json, ascii, maz)"))
+    }
+}
+
+impl fmt::Display for MazeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Serialize)]
+struct Fixture {
+    #[serde(flatten)]
+    maze: MazeJson,
+    hash: String,
+}
+
+// Only JSON carries the hash, the others are the bare maze
+pub fn write_maze_as<W: Write>(
+    writer: &mut W,
+    spec: &MazeSpec,
+    format: MazeFormat,
+) -> io::Result<()> {
+    let maze = maze_from_spec(spec);
+    match format {
+        MazeFormat::Json => {
+            let fixture = Fixture {
+                maze: maze_json(&maze),
+                hash: spec.hash(),
+            };
+            serde_json::to_writer(&mut *writer, &fixture)?;
+            writeln!(writer)
+        }
+        MazeFormat::Ascii => writer.write_all(maze.to_ascii().as_bytes()),
+        MazeFormat::Maz => writer.write_all(&maze.to_maz()),
+    }
+}
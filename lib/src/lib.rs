@@ -1,45 +1,252 @@
+mod analysis;
 mod app;
+mod bot;
+mod config;
+mod eval;
+mod gamepad;
+mod generate;
+mod limits;
+mod maze;
+mod optimize;
+mod process;
+mod race;
+mod render;
+mod replay;
+mod score;
 mod stdio;
-pub use app::MatahatanApp;
+mod tcp;
+mod world;
+pub use analysis::{
+    analyze, corner_velocity, estimate_frames, shortest_path, straight_runs, Analysis,
+};
+pub use app::{MatahatanApp, MatahatanAppState};
+pub use bot::{BotTuning, ReferenceBot};
+pub use config::{ConfigFile, LimitsSection, MazeSection, Transport};
+pub use eval::{evaluate, EvalConfig, EvalReport, EvalRun, EvalSummary};
+pub use gamepad::{Axis, Curve, GamepadMapping};
+pub use generate::{write_maze_as, MazeFormat};
+pub use limits::{Limits, Termination};
+pub use maze::{Coordinates, Direction, Field, Maze, Wall};
+pub use optimize::{lower_bound, optimize, Optimized};
+pub use process::{ProcessSolver, SolverExit, SolverLog};
+use race::mouse_collisions;
+pub use race::{Mouse, RaceResult, Racer, RacerResult};
+pub use render::{save_gif, save_png, Scene, MAX_PIXELS};
+pub use replay::Recording;
+pub use score::{RunStats, Score, ScoreConfig, ScoreMode};
 use std::sync::{Arc, Mutex};
-use stdio::{get_input, write_maze, write_output};
+use stdio::{write_json, StdioSolver};
+pub use stdio::{Input, Output};
+use world::World;
 
 use egui::{vec2, Vec2};
-use gamepads::Gamepads;
+use gamepads::{Gamepad, Gamepads};
 use maze_generator::ellers_algorithm::EllersGenerator;
 use maze_generator::growing_tree::GrowingTreeGenerator;
-use maze_generator::prelude::*;
+use maze_generator::prelude::Generator;
 use maze_generator::prims_algorithm::PrimsGenerator;
 use maze_generator::recursive_backtracking::RbGenerator;
 use ncollide2d::bounding_volume::HasBoundingVolume;
 use ncollide2d::math::{Isometry, Point, Vector};
-use ncollide2d::pipeline::object::{CollisionGroups, GeometricQueryType};
 use ncollide2d::query::PointQuery;
-use ncollide2d::shape::{Ball, Cuboid, ShapeHandle};
-use ncollide2d::world::CollisionWorld;
-use rand::Rng;
-use rand::RngCore;
+use ncollide2d::shape::Cuboid;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
 use std::{thread, time};
 
-const MAZE_X: i32 = 25;
-const MAZE_Y: i32 = 25;
+// Default width and height in cells
+pub const MAZE_SIZE: (i32, i32) = (25, 25);
+// Largest width and height, the recursive generators run out of stack and the
+// others take minutes beyond
+pub const MAX_MAZE_SIZE: i32 = 256;
+// More rooms or staircases than this only take longer to carve
+pub const MAX_ROOMS: u32 = 10_000;
+pub const MAX_STAIRS: u32 = 10_000;
+// Part of maze ids, to be raised when a spec builds another maze than before
+pub const MAZE_VERSION: u32 = 1;
 const PI: f32 = std::f32::consts::PI;
 
 const STEERING_SCALER: f32 = 0.4;
 const ACCELERATION_SCALER: f32 = 0.01;
+const BRAKE_SCALER: f32 = 5.0;
+const TURN_SCALER: f32 = 20.0;
+const MAX_VELOCITY: f32 = 0.2;
 
 #[derive(Clone)]
 pub struct Config {
     pub gui: bool,
     pub stdio: bool,
     pub stick: bool,
+    pub keyboard: bool,
+    // Command of a solver process driving the first mouse
+    pub solver: Option<String>,
+    pub gamepad: GamepadMapping,
     pub framerate: f32,
     pub seed: Option<String>,
     pub kind: MazeKind,
+    // Maze id, replaces seed, kind, size, braid, rooms and stairs
+    pub maze_id: Option<MazeSpec>,
+    pub size: (i32, i32),
+    pub braid: f32,
+    pub rooms: u32,
+    pub stairs: u32,
+    pub physics: PhysicsProfile,
+    pub bot: Option<Bot>,
+    pub ratio: bool,
+    pub limits: Limits,
+    pub score: ScoreConfig,
+    pub racers: Vec<Racer>,
+    pub mouse_collisions: bool,
+    // Save the run of the first mouse, show a recorded run as ghost
+    pub record: Option<String>,
+    pub ghost: Option<String>,
+    pub paused: bool,
+    // Replaces the preferences stored by the GUI
+    pub preferences: Option<MatahatanAppState>,
+    // Any maze option or id given, if not the GUI opens its last maze again
+    // and a ghost brings its own
+    pub maze_given: bool,
 }
 
-#[derive(Copy, Clone, PartialEq)]
+impl Config {
+    // The maze of the id or of the maze options
+    pub fn maze_spec(&self) -> MazeSpec {
+        match &self.maze_id {
+            Some(spec) => spec.clone(),
+            None => MazeSpec::new(&self.seed, self.kind)
+                .with_size(self.size)
+                .with_braid(self.braid)
+                .with_rooms(self.rooms)
+                .with_stairs(self.stairs),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Bot {
+    Reference,
+}
+
+// Anything that drives the mouse: gets the maze first, then each frame it is
+// asked for input and gets the output of the frame. Input should not block
+// past the deadline, None keeps the input of the last frame.
+pub trait Solver: Send {
+    fn maze(&mut self, maze: &Maze);
+    fn input(&mut self, deadline: Option<Instant>) -> Option<Input>;
+    fn output(&mut self, output: &Output);
+    fn status(&self) -> SolverStatus {
+        SolverStatus::Running
+    }
+    // Called with the result when its mouse stops, a process is ended and
+    // reports how
+    fn finish(&mut self, _result: &RunResult) -> Option<SolverExit> {
+        None
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SolverStatus {
+    Running,
+    TimedOut,
+    Exited,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunResult {
+    pub frames: i64,
+    pub goal: bool,
+    pub termination: Termination,
+    #[serde(flatten)]
+    pub stats: RunStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<Score>,
+    // Near lower bound of the frames for this maze and frames divided by it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bound: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ratio: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit: Option<SolverExit>,
+}
+
+impl RunResult {
+    pub fn new(state: &SimulationState, termination: Termination) -> Self {
+        RunResult {
+            frames: state.frame,
+            goal: state.goal,
+            termination,
+            stats: state.stats.clone(),
+            score: None,
+            bound: None,
+            ratio: None,
+            exit: None,
+        }
+    }
+
+    pub fn with_exit(mut self, exit: Option<SolverExit>) -> Self {
+        self.exit = exit;
+        self
+    }
+
+    pub fn with_score(mut self, config: &ScoreConfig) -> Self {
+        self.score = Some(config.score(&self.stats, self.frames, self.goal));
+        self
+    }
+
+    pub fn with_bound(self, maze: &Maze, physics: &PhysicsProfile) -> Self {
+        self.with_bound_frames(lower_bound(maze, physics))
+    }
+
+    pub fn with_bound_frames(mut self, bound: Option<i64>) -> Self {
+        if let Some(bound) = bound.filter(|bound| *bound > 0) {
+            self.bound = Some(bound);
+            self.ratio = Some(self.frames as f32 / bound as f32);
+        }
+        self
+    }
+}
+
+// The higher the velocity the larger the turning radius: the steering is
+// divided by max(1, velocity * turn_scaler). Braking is brake_scaler times
+// stronger than accelerating.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhysicsProfile {
+    pub max_velocity: f32,
+    pub steering_scaler: f32,
+    pub acceleration_scaler: f32,
+    pub brake_scaler: f32,
+    pub turn_scaler: f32,
+}
+
+impl Default for PhysicsProfile {
+    fn default() -> Self {
+        PhysicsProfile {
+            max_velocity: MAX_VELOCITY,
+            steering_scaler: STEERING_SCALER,
+            acceleration_scaler: ACCELERATION_SCALER,
+            brake_scaler: BRAKE_SCALER,
+            turn_scaler: TURN_SCALER,
+        }
+    }
+}
+
+impl PhysicsProfile {
+    // JSON file, missing fields keep their default
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MazeKind {
     Ellers,
     Backtracking,
@@ -48,6 +255,15 @@ pub enum MazeKind {
 }
 
 impl MazeKind {
+    pub fn all() -> [MazeKind; 4] {
+        [
+            MazeKind::Ellers,
+            MazeKind::Backtracking,
+            MazeKind::GrowingTree,
+            MazeKind::Prims,
+        ]
+    }
+
     pub fn random() -> Self {
         let mut rng = rand::thread_rng();
         match rng.gen_range(0..4) {
@@ -58,22 +274,72 @@ impl MazeKind {
             _ => unreachable!(),
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MazeKind::Ellers => "ellers",
+            MazeKind::Backtracking => "backtracking",
+            MazeKind::GrowingTree => "growing_tree",
+            MazeKind::Prims => "prims",
+        }
+    }
+}
+
+impl FromStr for MazeKind {
+    type Err = String;
+
+    fn from_str(kind_str: &str) -> Result<Self, Self::Err> {
+        MazeKind::all()
+            .into_iter()
+            .find(|kind| kind.name() == kind_str)
+            .ok_or_else(|| {
+                let names: Vec<&str> = MazeKind::all().iter().map(|kind| kind.name()).collect();
+                format!("unknown maze kind '{kind_str}' ({})", names.join(", "))
+            })
+    }
+}
+
+impl fmt::Display for MazeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 #[derive(Clone, PartialEq)]
 pub struct MazeSpec {
     seed: [u8; 32],
     kind: MazeKind,
+    size: (i32, i32),
+    braid: f32,
+    rooms: u32,
+    stairs: u32,
 }
 
 impl MazeSpec {
+    // Random maze of kind if there is no seed
+    pub fn new(seed: &Option<String>, kind: MazeKind) -> Self {
+        match seed {
+            Some(seed) => MazeSpec::from_string(seed, kind),
+            None => MazeSpec::random_with_kind(kind),
+        }
+    }
+
+    // Random seed and kind
     pub fn random() -> Self {
+        MazeSpec::random_with_kind(MazeKind::random())
+    }
+
+    pub fn random_with_kind(kind: MazeKind) -> Self {
         let mut rng = rand::thread_rng();
         let mut seed = [0u8; 32];
         rng.fill_bytes(&mut seed);
         MazeSpec {
-            seed: seed,
-            kind: MazeKind::random(),
+            seed,
+            kind,
+            size: MAZE_SIZE,
+            braid: 0.0,
+            rooms: 0,
+            stairs: 0,
         }
     }
 
@@ -84,31 +350,204 @@ impl MazeSpec {
         MazeSpec {
             seed: result.into(),
             kind,
+            size: MAZE_SIZE,
+            braid: 0.0,
+            rooms: 0,
+            stairs: 0,
+        }
+    }
+
+    // Width and height in cells, from 2 to MAX_MAZE_SIZE
+    pub fn with_size(mut self, size: (i32, i32)) -> Self {
+        self.size = (
+            size.0.clamp(2, MAX_MAZE_SIZE),
+            size.1.clamp(2, MAX_MAZE_SIZE),
+        );
+        self
+    }
+
+    pub fn with_braid(mut self, braid: f32) -> Self {
+        self.braid = braid;
+        self
+    }
+
+    pub fn with_rooms(mut self, rooms: u32) -> Self {
+        self.rooms = rooms.min(MAX_ROOMS);
+        self
+    }
+
+    pub fn with_stairs(mut self, stairs: u32) -> Self {
+        self.stairs = stairs.min(MAX_STAIRS);
+        self
+    }
+
+    // Another maze with the same size, braid, rooms and stairs, a random seed
+    // keeps the kind
+    pub fn with_seed(&self, seed: &Option<String>, kind: MazeKind) -> Self {
+        let spec = match seed {
+            Some(seed) => MazeSpec::from_string(seed, kind),
+            None => MazeSpec::random_with_kind(kind),
+        };
+        spec.with_size(self.size)
+            .with_braid(self.braid)
+            .with_rooms(self.rooms)
+            .with_stairs(self.stairs)
+    }
+
+    pub fn kind(&self) -> MazeKind {
+        self.kind
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        self.size
+    }
+
+    // Digest of everything that shapes the maze, equal hashes are equal mazes
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.seed);
+        hasher.update(self.kind.name());
+        hasher.update(self.size.0.to_le_bytes());
+        hasher.update(self.size.1.to_le_bytes());
+        hasher.update(self.braid.to_le_bytes());
+        hasher.update(self.rooms.to_le_bytes());
+        hasher.update(self.stairs.to_le_bytes());
+        hex(&hasher.finalize())
+    }
+}
+
+// The id is VERSION:KIND:WxH:SEED with the seed as 64 hex digits, followed by
+// :braid=F, :rooms=N and :stairs=N if they are set
+impl FromStr for MazeSpec {
+    type Err = String;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid maze id '{id}' (VERSION:KIND:WxH:SEED)");
+        let mut parts = id.trim().split(':');
+        let mut next = || parts.next().ok_or_else(invalid);
+        let version: u32 = next()?.parse().map_err(|_| invalid())?;
+        if version != MAZE_VERSION {
+            return Err(format!(
+                "maze id of generator version {version}, this is version {MAZE_VERSION}"
+            ));
+        }
+        let kind = next()?.parse::<MazeKind>()?;
+        let size = next()?
+            .split_once('x')
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            .ok_or_else(invalid)?;
+        let sizes = 2..=MAX_MAZE_SIZE;
+        if !sizes.contains(&size.0) || !sizes.contains(&size.1) {
+            return Err(format!(
+                "maze id of {}x{} cells, from 2x2 to {MAX_MAZE_SIZE}x{MAX_MAZE_SIZE}",
+                size.0, size.1
+            ));
+        }
+        let seed = unhex(next()?).ok_or_else(invalid)?;
+        let mut spec = MazeSpec {
+            seed,
+            kind,
+            size,
+            braid: 0.0,
+            rooms: 0,
+            stairs: 0,
+        };
+        for feature in parts {
+            match feature.split_once('=') {
+                Some(("braid", braid)) => {
+                    spec.braid = braid
+                        .parse()
+                        .ok()
+                        .filter(|braid| (0.0..=1.0).contains(braid))
+                        .ok_or_else(invalid)?
+                }
+                Some(("rooms", rooms)) => spec.rooms = rooms.parse().map_err(|_| invalid())?,
+                Some(("stairs", stairs)) => spec.stairs = stairs.parse().map_err(|_| invalid())?,
+                _ => return Err(invalid()),
+            }
+        }
+        if spec.rooms > MAX_ROOMS || spec.stairs > MAX_STAIRS {
+            return Err(format!(
+                "maze id with more than {MAX_ROOMS} rooms or {MAX_STAIRS} stairs"
+            ));
         }
+        Ok(spec)
     }
 }
 
+impl fmt::Display for MazeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (width, height) = self.size;
+        write!(
+            f,
+            "{MAZE_VERSION}:{}:{width}x{height}:{}",
+            self.kind,
+            hex(&self.seed)
+        )?;
+        if self.braid > 0.0 {
+            write!(f, ":braid={}", self.braid)?;
+        }
+        if self.rooms > 0 {
+            write!(f, ":rooms={}", self.rooms)?;
+        }
+        if self.stairs > 0 {
+            write!(f, ":stairs={}", self.stairs)?;
+        }
+        Ok(())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn unhex(text: &str) -> Option<[u8; 32]> {
+    if text.len() != 64 || !text.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (byte, digits) in bytes.iter_mut().zip(text.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
 #[derive(Clone)]
 pub struct SimulationConfig {
     framerate: f32,
-    steering_scaler: f32,
-    acceleration_scaler: f32,
+    // Frames are slept for, stdio-mode and FPS 0 run as fast as possible
+    paced: bool,
+    physics: PhysicsProfile,
     #[allow(dead_code)]
     zero: Vec2,
     #[allow(dead_code)]
     size: Vec2,
-    human: bool,
+    score: ScoreConfig,
+    mouse_collisions: bool,
 }
 
 impl SimulationConfig {
     pub fn new(config: &Config, size: Vec2) -> Self {
         SimulationConfig {
             framerate: config.framerate,
-            steering_scaler: STEERING_SCALER,
-            acceleration_scaler: ACCELERATION_SCALER,
+            paced: config.framerate > 0.0 && !config.stdio,
+            physics: config.physics.clone(),
             zero: vec2(0.0, 0.0),
             size,
-            human: config.stick,
+            score: config.score.clone(),
+            mouse_collisions: config.mouse_collisions,
+        }
+    }
+
+    pub fn headless(physics: &PhysicsProfile, size: Vec2) -> Self {
+        SimulationConfig {
+            framerate: 0.0,
+            paced: false,
+            physics: physics.clone(),
+            zero: vec2(0.0, 0.0),
+            size,
+            score: ScoreConfig::default(),
+            mouse_collisions: false,
         }
     }
 }
@@ -118,6 +557,8 @@ pub struct SimulationState {
     frame: i64,
     position: Vec2,
     collision: bool,
+    stats: RunStats,
+    goal: bool,
     velocity: f32,
     velocity_v: Vec2,
     angle_v: Vec2,
@@ -126,189 +567,676 @@ pub struct SimulationState {
     acceleration: f32, // input
 }
 
+impl SimulationState {
+    pub fn new() -> Self {
+        SimulationState {
+            frame: 0,
+            position: vec2(0.5, 0.5),
+            collision: false,
+            stats: RunStats::default(),
+            goal: false,
+            velocity: 0.0,
+            velocity_v: vec2(0.0, 0.0),
+            angle_v: vec2(0.0, 0.0),
+            angle: 0.0,
+            steering: 0.0,
+            acceleration: 0.0,
+        }
+    }
+}
+
+// Play/pause, stepping and speed set in the GUI. While paused the simulation
+// runs steps more frames.
+#[derive(Clone)]
+pub struct Control {
+    pub paused: bool,
+    pub steps: u32,
+    pub speed: f32,
+}
+
+// Per frame history for the trail and the telemetry in the GUI
+#[derive(Clone, Copy)]
+pub struct TrailPoint {
+    frame: i64,
+    position: Vec2,
+    angle: f32,
+    velocity: f32,
+    true_velocity: f32,
+    steering: f32,
+    acceleration: f32,
+    collision: bool,
+}
+
+impl TrailPoint {
+    pub fn new(state: &SimulationState) -> Self {
+        TrailPoint {
+            frame: state.frame,
+            position: state.position,
+            angle: state.angle,
+            velocity: state.velocity,
+            true_velocity: state.velocity_v.length(),
+            steering: state.steering,
+            acceleration: state.acceleration,
+            collision: state.collision,
+        }
+    }
+}
+
+impl Default for SimulationState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Arrow keys or WASD held in the GUI
+#[derive(Clone, Copy, Default)]
+pub struct Keys {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+// Keys ramp steering and acceleration like a stick, releasing returns to zero
+// faster. The curves of the gamepad mapping are applied after.
+#[derive(Default)]
+struct Keyboard {
+    steering: f32,
+    acceleration: f32,
+}
+
+impl Keyboard {
+    fn input(&mut self, keys: Keys) -> (f32, f32) {
+        (self.steering, self.acceleration) =
+            keyboard_ramp((self.steering, self.acceleration), keys);
+        (self.steering, self.acceleration)
+    }
+}
+
+// One frame of the keyboard ramp from the previous steering and acceleration,
+// opposite keys held together count as none.
+fn keyboard_ramp((steering, acceleration): (f32, f32), keys: Keys) -> (f32, f32) {
+    let axis = |negative: bool, positive: bool| match (negative, positive) {
+        (false, true) => 1.0,
+        (true, false) => -1.0,
+        _ => 0.0,
+    };
+    let ramp = |value: f32, target: f32| {
+        let rate = if target == 0.0 { 0.3 } else { 0.15 };
+        value + (target - value).clamp(-rate, rate)
+    };
+    (
+        ramp(steering, axis(keys.left, keys.right)),
+        ramp(acceleration, axis(keys.down, keys.up)),
+    )
+}
+
+// Read once per frame for the stick and keyboard drivers
+struct Human {
+    keys: Keys,
+    gamepads: Vec<Gamepad>,
+    mapping: GamepadMapping,
+}
+
+// Input source of a mouse, a stick is the id of a controller, which it keeps
+// when others disconnect
+enum Driver {
+    Stick(u8),
+    Keyboard(Keyboard),
+    Solver(Box<dyn Solver>),
+    Idle,
+}
+
 pub struct LocalState {
     config: Config,
+    drivers: Vec<Driver>,
+    // Only opened if a mouse is driven by a stick
     gamepads: Option<Gamepads>,
-    maze: Maze,
     shared_state: Arc<Mutex<SharedState>>,
-    world: CollisionWorld<f32, ()>,
-    ball: Ball<f32>,
-    wall: Cuboid<f32>,
-    active: CollisionGroups,
-    passive: CollisionGroups,
-    query_type: GeometricQueryType<f32>,
+    world: World,
 }
 
 impl LocalState {
-    pub fn new(config: &Config, maze: Maze, shared_state: Arc<Mutex<SharedState>>) -> Self {
-        let gamepads;
-        if config.stick {
-            gamepads = Some(Gamepads::new());
-        } else {
-            gamepads = None;
-        }
-        let mut active = CollisionGroups::new();
-        active.set_membership(&[1]);
-        let mut passive = CollisionGroups::new();
-        passive.set_membership(&[2]);
-        passive.set_whitelist(&[1]);
+    fn new(
+        config: &Config,
+        maze: Maze,
+        drivers: Vec<Driver>,
+        shared_state: Arc<Mutex<SharedState>>,
+    ) -> Self {
+        let sticks = drivers
+            .iter()
+            .any(|driver| matches!(driver, Driver::Stick(_)));
         LocalState {
             config: config.clone(),
-            gamepads,
-            maze,
+            gamepads: sticks.then(Gamepads::new),
+            drivers,
             shared_state,
-            world: CollisionWorld::new(0.05),
-            ball: Ball::new(0.15),
-            wall: Cuboid::new(Vector::new(0.6, 0.1)),
-            active,
-            passive,
-            query_type: GeometricQueryType::Contacts(0.0, 0.0),
+            world: World::new(maze),
+        }
+    }
+}
+
+// The mouse of stdio/stick/bot comes first, then the racers. Without any of
+// them there is one idle mouse, so the maze can be looked at.
+fn drivers(config: &Config, maze: &Maze) -> (Vec<Driver>, Vec<Mouse>) {
+    let mut drivers = Vec::new();
+    let mut mice = Vec::new();
+    let log = SolverLog::default();
+    let mut failed = false;
+    let primary = if config.stick {
+        Some(("stick", Driver::Stick(0)))
+    } else if config.keyboard {
+        Some(("keyboard", Driver::Keyboard(Keyboard::default())))
+    } else if let Some(command) = &config.solver {
+        let driver = match ProcessSolver::spawn_logged(command, log.clone(), !config.gui) {
+            Ok(solver) => Driver::Solver(Box::new(solver)),
+            Err(error) => {
+                eprintln!("failed to start solver: {error}");
+                failed = true;
+                Driver::Idle
+            }
+        };
+        Some(("solver", driver))
+    } else if config.stdio {
+        Some(("stdio", Driver::Solver(Box::new(StdioSolver::new()))))
+    } else {
+        match config.bot {
+            Some(Bot::Reference) => Some((
+                "reference",
+                Driver::Solver(Box::new(ReferenceBot::new(&config.physics))),
+            )),
+            None if config.racers.is_empty() => Some(("mouse", Driver::Idle)),
+            None => None,
+        }
+    };
+    if let Some((name, driver)) = primary {
+        let mut mouse = Mouse::new(name.to_string(), maze);
+        mouse.log = log;
+        if failed {
+            mouse.termination = Some(Termination::SpawnFailed);
         }
+        drivers.push(driver);
+        mice.push(mouse);
     }
+    for racer in &config.racers {
+        let mut mouse = Mouse::new(racer.name(), maze);
+        if let Racer::Stick(id) = racer {
+            drivers.push(Driver::Stick(*id));
+            mice.push(mouse);
+            continue;
+        }
+        match racer.solver(&config.physics, &mouse.log, !config.gui) {
+            Ok(solver) => drivers.push(Driver::Solver(solver)),
+            Err(error) => {
+                eprintln!("failed to start {}: {error}", racer.name());
+                mouse.termination = Some(Termination::SpawnFailed);
+                drivers.push(Driver::Idle);
+            }
+        }
+        mice.push(mouse);
+    }
+    (drivers, mice)
 }
 
 pub struct SharedState {
     ctx: Option<egui::Context>,
     maze_spec: MazeSpec,
-    simulation: SimulationState,
+    mice: Vec<Mouse>,
+    // One per mouse, only grows, so the GUI copies what is new
+    trails: Vec<Vec<TrailPoint>>,
+    ghost: Option<Recording>,
+    control: Control,
+    // Set by the GUI, the simulation starts a new run with it
+    restart: Option<MazeSpec>,
+    // Counts restarts, so the GUI knows to reset its copies
+    run: u64,
+    // Set when the window is gone
+    closed: bool,
+    keys: Keys,
+    // Edited in the calibration window
+    gamepad: GamepadMapping,
+    // From the configuration file, taken by the GUI on startup
+    preferences: Option<MatahatanAppState>,
+    // The GUI picks the first maze, the simulation waits for it
+    last_maze: bool,
     config: SimulationConfig,
 }
 
 impl SharedState {
-    pub fn new(config: &Config, maze_spec: MazeSpec, size: Vec2) -> Self {
+    pub fn new(config: &Config, maze_spec: MazeSpec, size: Vec2, mice: Vec<Mouse>) -> Self {
         SharedState {
             ctx: None,
             maze_spec,
-            simulation: SimulationState {
-                frame: 0,
-                position: vec2(0.5, 0.5),
-                collision: false,
-                velocity: 0.0,
-                velocity_v: vec2(0.0, 0.0),
-                angle_v: vec2(0.0, 0.0),
-                angle: 0.0,
-                steering: 0.0,
-                acceleration: 0.0,
+            trails: vec![Vec::new(); mice.len()],
+            mice,
+            ghost: None,
+            control: Control {
+                paused: config.paused,
+                steps: 0,
+                speed: 1.0,
             },
+            restart: None,
+            run: 0,
+            closed: false,
+            keys: Keys::default(),
+            gamepad: config.gamepad.clone(),
+            preferences: config.preferences.clone(),
+            last_maze: config.gui && !config.maze_given,
             config: SimulationConfig::new(config, size),
         }
     }
 }
 
-pub fn run_simulation(config: &Config) {
-    let maze_spec;
-    if let Some(seed) = &config.seed {
-        maze_spec = MazeSpec::from_string(seed, config.kind);
-    } else {
-        maze_spec = MazeSpec::random();
-    }
-    let maze_spec2 = maze_spec.clone();
-    let maze = maze_from_seed_and_kind(maze_spec2.seed, maze_spec2.kind);
-    if config.stdio {
-        write_maze(&maze);
-    }
-    let size = vec2(maze.size.1 as f32, maze.size.0 as f32);
-    let shared_state = Arc::new(Mutex::new(SharedState::new(
-        config,
-        maze_spec.clone(),
-        size,
-    )));
-    let mut local_state = LocalState::new(config, maze, shared_state.clone());
-    add_maze(&mut local_state);
-    local_state.world.update();
-    let handle = thread::spawn(move || {
-        simulation_loop(&mut local_state);
-    });
+pub fn run_simulation(config: &Config) -> std::io::Result<()> {
+    let ghost = match &config.ghost {
+        Some(path) => Some(Recording::load(path).map_err(|error| {
+            std::io::Error::new(
+                error.kind(),
+                format!("failed to load ghost {path}: {error}"),
+            )
+        })?),
+        None => None,
+    };
+    let ghost_maze = ghost
+        .as_ref()
+        .and_then(|ghost| ghost.maze.clone())
+        .filter(|_| !config.maze_given);
+    let maze_spec = ghost_maze.clone().unwrap_or_else(|| config.maze_spec());
+    if let Some(ghost) = &ghost {
+        ghost.check(&maze_spec)?;
+    }
+    // Without a window a random maze could not be run again otherwise
+    if !config.gui && config.seed.is_none() && config.maze_id.is_none() && ghost_maze.is_none() {
+        eprintln!("maze {maze_spec}");
+    }
+    let maze = maze_from_spec(&maze_spec);
+    let size = vec2(maze.size.0 as f32, maze.size.1 as f32);
+    let (drivers, mice) = drivers(config, &maze);
+    let shared_state = Arc::new(Mutex::new(SharedState::new(config, maze_spec, size, mice)));
+    {
+        let mut state = shared_state.lock().unwrap();
+        state.ghost = ghost;
+        // Its maze, not the last one of the GUI
+        state.last_maze &= ghost_maze.is_none();
+    }
+    let mut local_state = LocalState::new(config, maze, drivers, shared_state.clone());
+    let handle = thread::spawn(move || simulation_loop(&mut local_state));
     if config.gui {
-        show_maze(shared_state).unwrap();
+        show_maze(shared_state.clone()).unwrap();
+        shared_state.lock().unwrap().closed = true;
     }
     handle.join().unwrap();
+    Ok(())
+}
+
+// Written when a run ends. In stdio-mode the result is the last message the
+// solver gets for the maze, the race goes to stderr then.
+fn result_bound(config: &Config, maze: &Maze) -> Option<i64> {
+    if config.ratio {
+        lower_bound(maze, &config.physics)
+    } else {
+        None
+    }
 }
 
+// Without how its solver ended
+fn mouse_result(config: &Config, bound: Option<i64>, mouse: &Mouse) -> RunResult {
+    let termination = mouse.termination.unwrap_or(Termination::Closed);
+    RunResult::new(&mouse.simulation, termination)
+        .with_score(&config.score)
+        .with_bound_frames(bound)
+}
+
+// A solver gets its result and a solver process ends with every run, a
+// restart starts it again
+fn stop_mouse(config: &Config, maze: &Maze, mouse: &mut Mouse, driver: &mut Driver) {
+    if let Driver::Solver(solver) = driver {
+        let result = mouse_result(config, result_bound(config, maze), mouse);
+        mouse.exit = solver.finish(&result);
+    }
+}
+
+fn racer_results(config: &Config, maze: &Maze, mice: &[Mouse]) -> Vec<RacerResult> {
+    let bound = result_bound(config, maze);
+    mice.iter()
+        .map(|mouse| RacerResult {
+            name: mouse.name.clone(),
+            result: mouse_result(config, bound, mouse).with_exit(mouse.exit.clone()),
+        })
+        .collect()
+}
+
+fn write_results(config: &Config, maze: &Maze, mice: &[Mouse]) {
+    let racers = racer_results(config, maze, mice);
+    if racers.len() == 1 {
+        write_json(&racers[0].result);
+    } else if config.stdio {
+        write_json(&racers[0].result);
+        if let Ok(race) = serde_json::to_string(&RaceResult::new(racers)) {
+            eprintln!("{race}");
+        }
+    } else {
+        write_json(&RaceResult::new(racers));
+    }
+}
+
+// With GUI a finished run waits for a restart (or the window to close)
 fn simulation_loop(local_state: &mut LocalState) {
-    let mut running = true;
+    let mut closed = false;
+    let limits = local_state.config.limits.clone();
     let shared_state = local_state.shared_state.clone();
     let config;
-    let sleep_time;
+    let mut mice;
+    let last_maze;
     {
         let state = shared_state.lock().unwrap();
         config = state.config.clone();
-        sleep_time = time::Duration::from_secs_f32(1.0 / config.framerate);
+        mice = state.mice.clone();
+        last_maze = state.last_maze;
     }
-    while running {
-        let mut simulation;
-        {
+    let reopened = if last_maze {
+        wait_for_maze(&shared_state)
+    } else {
+        None
+    };
+    match reopened {
+        Some(maze_spec) => restart_run(local_state, &maze_spec, &mut mice),
+        None => {
+            for driver in &mut local_state.drivers {
+                if let Driver::Solver(solver) = driver {
+                    solver.maze(&local_state.world.maze);
+                }
+            }
+        }
+    }
+    let mut start = Instant::now();
+    let sleep_time = if config.paced {
+        Some(time::Duration::from_secs_f32(1.0 / config.framerate))
+    } else {
+        None
+    };
+    let mut recording = Recording::new(&shared_state.lock().unwrap().maze_spec);
+    loop {
+        // Time limits do not count the pause
+        let (waited, speed) = wait_while_paused(&shared_state, &mut closed);
+        start += waited;
+        let restart = shared_state.lock().unwrap().restart.take();
+        if let Some(maze_spec) = restart.filter(|_| !closed) {
+            if mice.iter().any(|mouse| mouse.running()) {
+                for (mouse, driver) in mice
+                    .iter_mut()
+                    .zip(&mut local_state.drivers)
+                    .filter(|(mouse, _)| mouse.running())
+                {
+                    mouse.termination = Some(Termination::Restarted);
+                    stop_mouse(&local_state.config, &local_state.world.maze, mouse, driver);
+                }
+                finish_run(local_state, &mice, &recording);
+            }
+            restart_run(local_state, &maze_spec, &mut mice);
+            recording = Recording::new(&maze_spec);
+            start = Instant::now();
+        }
+        if !mice.iter().any(|mouse| mouse.running()) {
+            if closed || !local_state.config.gui {
+                return;
+            }
+            thread::sleep(time::Duration::from_millis(10));
+            continue;
+        }
+        let human = {
             let state = shared_state.lock().unwrap();
-            simulation = state.simulation.clone();
+            let gamepads = match &mut local_state.gamepads {
+                Some(gamepads) => {
+                    gamepads.poll();
+                    gamepads.all().collect()
+                }
+                None => Vec::new(),
+            };
+            Human {
+                keys: state.keys,
+                gamepads,
+                mapping: state.gamepad.clone(),
+            }
+        };
+        let world = &local_state.world;
+        let mut stepped = vec![false; mice.len()];
+        for ((mouse, driver), stepped) in mice
+            .iter_mut()
+            .zip(&mut local_state.drivers)
+            .zip(&mut stepped)
+        {
+            if mouse.running() {
+                let deadline = limits.deadline(start);
+                *stepped = input_step(driver, &mut mouse.simulation, &human, deadline);
+                if *stepped {
+                    simulation_step(world, &config, &mut mouse.simulation);
+                }
+            }
+        }
+        if config.mouse_collisions {
+            mouse_collisions(&mut mice, world.ball.radius);
+        }
+        for (mouse, _) in mice
+            .iter_mut()
+            .zip(&stepped)
+            .filter(|(_, stepped)| **stepped)
+        {
+            update_state(world, &mut mouse.simulation);
+            mouse.enter(&world.maze);
+        }
+        if let (Some(mouse), Some(true)) = (mice.first(), stepped.first()) {
+            recording.frames.push(Output::new(&mouse.simulation));
         }
-        input_step(local_state, &mut simulation);
-        simulation_step(local_state, &config, &mut simulation);
         {
             let mut state = shared_state.lock().unwrap();
-            state.simulation = simulation.clone();
+            for ((mouse, trail), stepped) in mice.iter().zip(&mut state.trails).zip(&stepped) {
+                if *stepped {
+                    trail.push(TrailPoint::new(&mouse.simulation));
+                }
+            }
+            state.mice = mice.clone();
+            closed |= close_requested(&state);
             if let Some(ctx) = &state.ctx {
-                ctx.input(|s| {
-                    if s.viewport().close_requested() {
-                        running = false;
-                    }
-                });
                 ctx.request_repaint();
             }
         }
-        if local_state.config.stdio {
-            write_output(&simulation);
+        for ((mouse, driver), stepped) in
+            mice.iter_mut().zip(&mut local_state.drivers).zip(&stepped)
+        {
+            if !mouse.running() {
+                continue;
+            }
+            let status = match driver {
+                Driver::Solver(solver) => {
+                    if *stepped {
+                        solver.output(&Output::new(&mouse.simulation));
+                    }
+                    solver.status()
+                }
+                _ => SolverStatus::Running,
+            };
+            mouse.termination = limits.check(&mouse.simulation, status, start);
+            if closed {
+                mouse.termination = mouse.termination.or(Some(Termination::Closed));
+            }
+            if mouse.termination.is_some() {
+                stop_mouse(&local_state.config, &world.maze, mouse, driver);
+            }
+        }
+        if !mice.iter().any(|mouse| mouse.running()) {
+            shared_state.lock().unwrap().mice = mice.clone();
+            finish_run(local_state, &mice, &recording);
         }
-        if !local_state.config.stdio {
-            thread::sleep(sleep_time);
+        if let Some(sleep_time) = sleep_time {
+            thread::sleep(sleep_time.div_f32(speed));
         }
     }
 }
 
-fn input_step(local_state: &mut LocalState, simulation: &mut SimulationState) {
-    if let Some(gamepads) = &mut local_state.gamepads {
-        gamepads.poll();
+fn finish_run(local_state: &LocalState, mice: &[Mouse], recording: &Recording) {
+    write_results(&local_state.config, &local_state.world.maze, mice);
+    if let Some(path) = &local_state.config.record {
+        if let Err(error) = recording.save(path) {
+            eprintln!("failed to save recording {path}: {error}");
+        }
+    }
+}
 
-        for gamepad in gamepads.all() {
-            let ls = gamepad.left_stick();
-            let rs = gamepad.right_stick();
-            simulation.steering = (ls.0 + rs.0).min(1.0).max(-1.0);
-            simulation.acceleration = (ls.1 + rs.1).min(1.0).max(-1.0);
+// New maze (or the same again): fresh mice and collision world, every solver
+// gets the new maze header
+fn restart_run(local_state: &mut LocalState, maze_spec: &MazeSpec, mice: &mut [Mouse]) {
+    let maze = maze_from_spec(maze_spec);
+    for (mouse, driver) in mice.iter_mut().zip(&mut local_state.drivers) {
+        let failed = mouse.termination == Some(Termination::SpawnFailed);
+        let log = mouse.log.clone();
+        *mouse = Mouse::new(mouse.name.clone(), &maze);
+        mouse.log = log;
+        if failed {
+            mouse.termination = Some(Termination::SpawnFailed);
         }
-    } else if local_state.config.stdio {
-        if let Some(input) = get_input() {
-            simulation.steering = input.steering.min(1.0).max(-1.0);
-            simulation.acceleration = input.acceleration.min(1.0).max(-1.0);
-        } else {
-            let sleep_time = time::Duration::from_secs_f32(0.1);
-            thread::sleep(sleep_time);
+        if let Driver::Solver(solver) = driver {
+            solver.maze(&maze);
         }
     }
+    local_state.world = World::new(maze);
+    let mut state = local_state.shared_state.lock().unwrap();
+    state.maze_spec = maze_spec.clone();
+    state.mice = mice.to_vec();
+    state.trails = vec![Vec::new(); mice.len()];
+    state.run += 1;
 }
 
-fn simulation_step(
-    local_state: &LocalState,
-    config: &SimulationConfig,
-    state: &mut SimulationState,
-) {
-    state.collision = false;
-    let max_velocity = 0.2;
-    state.frame += 1;
-    if config.human {
-        if state.steering.abs() < 0.2 {
-            state.steering = 0.0;
+// Set by the GUI when it starts, None if the window was closed before
+fn wait_for_maze(shared_state: &Arc<Mutex<SharedState>>) -> Option<MazeSpec> {
+    loop {
+        {
+            let mut state = shared_state.lock().unwrap();
+            if let Some(maze_spec) = state.restart.take() {
+                return Some(maze_spec);
+            }
+            if close_requested(&state) {
+                return None;
+            }
         }
-        state.steering = state.steering.powi(7);
-        state.acceleration = state.acceleration.powi(3);
+        thread::sleep(time::Duration::from_millis(10));
     }
+}
+
+fn close_requested(state: &SharedState) -> bool {
+    match &state.ctx {
+        Some(ctx) => state.closed || ctx.input(|s| s.viewport().close_requested()),
+        None => state.closed,
+    }
+}
+
+// Returns how long we waited and the speed multiplier
+fn wait_while_paused(
+    shared_state: &Arc<Mutex<SharedState>>,
+    closed: &mut bool,
+) -> (time::Duration, f32) {
+    let begin = Instant::now();
+    loop {
+        {
+            let mut state = shared_state.lock().unwrap();
+            *closed |= close_requested(&state);
+            let control = &mut state.control;
+            if !control.paused || *closed {
+                return (begin.elapsed(), control.speed.max(0.01));
+            }
+            if control.steps > 0 {
+                control.steps -= 1;
+                return (begin.elapsed(), control.speed.max(0.01));
+            }
+        }
+        thread::sleep(time::Duration::from_millis(10));
+    }
+}
+
+// False if the solver timed out or exited instead of answering, the frame is
+// not simulated then
+fn input_step(
+    driver: &mut Driver,
+    simulation: &mut SimulationState,
+    human: &Human,
+    deadline: Option<Instant>,
+) -> bool {
+    match driver {
+        Driver::Stick(id) => {
+            let gamepad = human
+                .gamepads
+                .iter()
+                .find(|gamepad| gamepad.id().value() == *id);
+            if let Some(gamepad) = gamepad {
+                (simulation.steering, simulation.acceleration) = human.mapping.input(gamepad);
+            }
+        }
+        Driver::Keyboard(keyboard) => {
+            let (steering, acceleration) = keyboard.input(human.keys);
+            (simulation.steering, simulation.acceleration) =
+                human.mapping.shape(steering, acceleration);
+        }
+        Driver::Solver(solver) => {
+            if let Some(input) = solver.input(deadline) {
+                simulation.steering = input.steering.clamp(-1.0, 1.0);
+                simulation.acceleration = input.acceleration.clamp(-1.0, 1.0);
+            }
+            return solver.status() == SolverStatus::Running;
+        }
+        Driver::Idle => (),
+    }
+    true
+}
+
+// Runs a solver without GUI, threads or sleeping. Stops at the goal or when a
+// limit is hit, without limits a solver that never finds the goal runs forever.
+pub fn run_headless(
+    maze: &Maze,
+    physics: &PhysicsProfile,
+    solver: &mut dyn Solver,
+    limits: &Limits,
+) -> RunResult {
+    let world = World::new(maze.clone());
+    let size = vec2(maze.size.0 as f32, maze.size.1 as f32);
+    let config = SimulationConfig::headless(physics, size);
+    let mut simulation = SimulationState::new();
+    let start = Instant::now();
+    solver.maze(maze);
+    loop {
+        let input = solver.input(limits.deadline(start));
+        // Without an answer the frame does not count, a solver timing out
+        // before its first input ends with 0 frames
+        if solver.status() == SolverStatus::Running {
+            if let Some(input) = input {
+                simulation.steering = input.steering.clamp(-1.0, 1.0);
+                simulation.acceleration = input.acceleration.clamp(-1.0, 1.0);
+            }
+            simulation_step(&world, &config, &mut simulation);
+            update_state(&world, &mut simulation);
+            solver.output(&Output::new(&simulation));
+        }
+        if let Some(termination) = limits.check(&simulation, solver.status(), start) {
+            let result = RunResult::new(&simulation, termination);
+            let exit = solver.finish(&result);
+            return result.with_exit(exit);
+        }
+    }
+}
+
+fn simulation_step(world: &World, config: &SimulationConfig, state: &mut SimulationState) {
+    state.collision = false;
+    let physics = &config.physics;
+    let max_velocity = physics.max_velocity;
+    state.frame += 1;
     if state.acceleration.signum() < 0.0 {
-        state.velocity += state.acceleration * config.acceleration_scaler * 5.0;
+        state.velocity += state.acceleration * physics.acceleration_scaler * physics.brake_scaler;
     } else {
-        state.velocity += state.acceleration * config.acceleration_scaler;
+        state.velocity += state.acceleration * physics.acceleration_scaler;
     }
     state.velocity = state.velocity.max(0.0);
     state.velocity = state.velocity.min(max_velocity);
-    let vel_scale = (state.velocity.abs() * 20.0).max(1.0);
-    state.angle += state.steering * config.steering_scaler / vel_scale;
+    let vel_scale = (state.velocity.abs() * physics.turn_scaler).max(1.0);
+    state.angle += state.steering * physics.steering_scaler / vel_scale;
     state.angle_v = Vec2::angled(state.angle);
     state.velocity_v = state.angle_v * state.velocity;
     let mut vel = state.velocity_v;
@@ -316,24 +1244,19 @@ fn simulation_step(
     let velocity_v = Vector::new(state.velocity_v.x, state.velocity_v.y);
     let trans_vec = Vector::new(pos.x, pos.y);
     let trans_matrix = Isometry::new(trans_vec, 0.0);
-    let aabb = local_state.ball.bounding_volume(&trans_matrix);
+    let aabb = world.ball.bounding_volume(&trans_matrix);
     // interference with ray crashes, I do not want to debug, interference with
     // aabb is less safe, hence max_velocity, so we don't skip walls. Maybe it
     // is fun for contestants to find collision-bugs.
-    let interferences = local_state
-        .world
-        .interferences_with_aabb(&aabb, &local_state.active);
+    let interferences = world.world.interferences_with_aabb(&aabb, &world.active);
     let mut found = false;
     for interference in interferences {
         if let Some(shape) = interference.1.shape().as_shape::<Cuboid<f32>>() {
             let origin = Point::new(pos.x, pos.y);
             let closest_point = shape
-                .project_point(&interference.1.position(), &origin, true)
+                .project_point(interference.1.position(), &origin, true)
                 .point;
-            if local_state
-                .ball
-                .contains_point(&trans_matrix, &closest_point)
-            {
+            if world.ball.contains_point(&trans_matrix, &closest_point) {
                 // TODO move ball outside point
                 let direction = closest_point - origin;
                 if direction.angle(&velocity_v) < PI / 2.0 {
@@ -357,6 +1280,28 @@ fn simulation_step(
     state.position += vel;
 }
 
+// Goal and counters, after all mice have moved
+fn update_state(world: &World, state: &mut SimulationState) {
+    let cell: Coordinates = (
+        state.position.x.floor() as i32,
+        state.position.y.floor() as i32,
+    )
+        .into();
+    state.goal = cell == world.maze.goal;
+    state
+        .stats
+        .update(state.collision, world.maze.is_inside(&cell));
+}
+
+pub fn run_analysis(maze_spec: &MazeSpec, physics: &PhysicsProfile, optimized: bool) {
+    let maze = maze_from_spec(maze_spec);
+    let mut analysis = analyze(&maze, physics);
+    if optimized {
+        analysis.optimized_frames = optimize(&maze, physics).map(|o| o.frames);
+    }
+    write_json(&analysis);
+}
+
 fn show_maze(shared_state: Arc<Mutex<SharedState>>) -> eframe::Result<()> {
     env_logger::init();
 
@@ -377,72 +1322,789 @@ fn show_maze(shared_state: Arc<Mutex<SharedState>>) -> eframe::Result<()> {
     )
 }
 
-fn add_maze(state: &mut LocalState) {
-    let x = state.maze.size.1;
-    let y = state.maze.size.0;
-    for ix in 0..x {
-        for iy in 0..y {
-            if let Some(field) = state.maze.get_field(&(ix, iy).into()) {
-                add_field(state, &field);
-            }
-        }
-    }
-    // Outer wall
-}
-
-fn add_field(state: &mut LocalState, field: &Field) {
-    if !field.has_passage(&Direction::West) {
-        add_wall(state, field, true);
-    }
-    if !field.has_passage(&Direction::North) {
-        add_wall(state, field, false);
-    }
-}
-
-fn add_wall(state: &mut LocalState, field: &Field, vertical: bool) {
-    let wall_shape = ShapeHandle::new(state.wall);
-    let angle;
-    let mut x = field.coordinates.x as f32;
-    let mut y = field.coordinates.y as f32;
-    if vertical {
-        angle = std::f32::consts::PI / 2.0;
-        y += 0.5;
-    } else {
-        angle = 0.0;
-        x += 0.5;
+pub fn maze_from_spec(spec: &MazeSpec) -> Maze {
+    let mut maze = maze_from_seed_and_kind(spec.seed, spec.kind, spec.size);
+    let mut rng = StdRng::from_seed(spec.seed);
+    maze.add_rooms(spec.rooms, &mut rng);
+    maze.add_staircases(spec.stairs, &mut rng);
+    if spec.braid > 0.0 {
+        maze.braid(spec.braid, &mut rng);
     }
-    let wall_position = Isometry::new(Vector::new(x, y), angle);
-    state.world.add(
-        wall_position,
-        wall_shape,
-        state.passive,
-        state.query_type,
-        (),
-    );
+    maze.id = Some(spec.to_string());
+    maze
 }
 
-pub fn maze_from_seed_and_kind(seed: [u8; 32], kind: MazeKind) -> Maze {
-    match kind {
+pub fn maze_from_seed_and_kind(seed: [u8; 32], kind: MazeKind, size: (i32, i32)) -> Maze {
+    let (width, height) = size;
+    let maze = match kind {
         MazeKind::Backtracking => {
             let mut generator = RbGenerator::new(Some(seed));
-            generator.generate(MAZE_X, MAZE_Y).unwrap()
+            generator.generate(width, height).unwrap()
         }
         MazeKind::Ellers => {
             let mut generator = EllersGenerator::new(Some(seed));
-            generator.generate(MAZE_X, MAZE_Y).unwrap()
+            generator.generate(width, height).unwrap()
         }
         MazeKind::GrowingTree => {
             let mut generator = GrowingTreeGenerator::new(Some(seed));
-            generator.generate(MAZE_X, MAZE_Y).unwrap()
+            generator.generate(width, height).unwrap()
         }
         MazeKind::Prims => {
             let mut generator = PrimsGenerator::new(Some(seed));
-            generator.generate(MAZE_X, MAZE_Y).unwrap()
+            generator.generate(width, height).unwrap()
         }
-    }
+    };
+    let mut maze = Maze::from(&maze);
+    maze.kind = Some(kind);
+    maze
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Write;
+    use std::time::Duration;
+    use stdio::maze_json;
+
+    #[test]
+    fn braid_removes_dead_ends() {
+        let spec = MazeSpec::from_string(&"braid".to_string(), MazeKind::Backtracking);
+        let perfect = maze_from_spec(&spec);
+        let braided = maze_from_spec(&spec.clone().with_braid(1.0));
+        assert!(!perfect.dead_ends().is_empty());
+        assert!(braided.dead_ends().is_empty());
+        let half = maze_from_spec(&spec.with_braid(0.5));
+        let expected =
+            perfect.dead_ends().len() - (perfect.dead_ends().len() as f32 * 0.5).round() as usize;
+        assert_eq!(half.dead_ends().len(), expected);
+    }
+
+    #[test]
+    fn room_has_no_interior_walls() {
+        let spec = MazeSpec::from_string(&"rooms".to_string(), MazeKind::Prims);
+        let mut maze = maze_from_spec(&spec);
+        maze.carve_room(&(3, 4).into(), 3, 2);
+        let center = maze.get_field(&(4, 4).into()).unwrap();
+        assert!(center.has_passage(&Direction::West));
+        assert!(center.has_passage(&Direction::East));
+        assert!(center.has_passage(&Direction::South));
+        let walls = maze.walls();
+        assert!(!walls.iter().any(|w| w.x == 4 && w.y == 5 && !w.vertical));
+        assert!(walls.iter().any(|w| w.x == 25 && w.y == 0 && w.vertical));
+    }
+
+    #[test]
+    fn analysis_follows_shortest_path() {
+        let spec = MazeSpec::from_string(&"analysis".to_string(), MazeKind::Ellers);
+        let maze = maze_from_spec(&spec);
+        let path = shortest_path(&maze).unwrap();
+        assert_eq!(path.first(), Some(&maze.start));
+        assert_eq!(path.last(), Some(&maze.goal));
+        let analysis = analyze(&maze, &PhysicsProfile::default());
+        assert_eq!(analysis.path_length, path.len() - 1);
+        assert_eq!(
+            straight_runs(&path).iter().sum::<usize>(),
+            analysis.path_length
+        );
+        assert!(
+            analysis.estimated_frames as f32 * MAX_VELOCITY >= analysis.path_length as f32 - 0.5
+        );
+    }
+
+    #[test]
+    fn bound_is_below_the_reference_bot() {
+        let physics = PhysicsProfile::default();
+        for (seed, kind) in [("a", MazeKind::Backtracking), ("bound", MazeKind::Prims)] {
+            let maze = maze_from_spec(&MazeSpec::from_string(&seed.to_string(), kind));
+            let bound = lower_bound(&maze, &physics).unwrap();
+            let mut bot = ReferenceBot::new(&physics);
+            let result = run_headless(&maze, &physics, &mut bot, &Limits::default());
+            assert!(result.goal);
+            assert!(bound > 0 && bound < result.frames);
+            // Slower cars need more frames
+            let slow = PhysicsProfile {
+                max_velocity: 0.1,
+                ..physics.clone()
+            };
+            assert!(lower_bound(&maze, &slow).unwrap() > bound);
+        }
+    }
+
+    // Like run_simulation without a window
+    fn race(config: &Config) -> RaceResult {
+        let maze_spec = config.maze_spec();
+        let maze = maze_from_spec(&maze_spec);
+        let size = vec2(maze.size.0 as f32, maze.size.1 as f32);
+        let (drivers, mice) = drivers(config, &maze);
+        let shared_state = Arc::new(Mutex::new(SharedState::new(config, maze_spec, size, mice)));
+        let mut local_state = LocalState::new(config, maze.clone(), drivers, shared_state.clone());
+        simulation_loop(&mut local_state);
+        let mice = shared_state.lock().unwrap().mice.clone();
+        RaceResult::new(racer_results(config, &maze, &mice))
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn race_with_and_without_mouse_collisions() {
+        let file = ConfigFile::parse(
+            r#"
+            gui = false
+            bot = "reference"
+            racers = ["bot:reference", "yes '{\"acceleration\":0,\"steering\":0}'"]
+
+            [maze]
+            seed = "a"
+
+            [limits]
+            max_frames = 300
+            "#,
+        )
+        .unwrap();
+        let config = file.config().unwrap();
+        let apart = race(&config);
+        assert_eq!(apart.winner.as_deref(), Some("reference"));
+        let [first, second, parked] = &apart.racers[..] else {
+            panic!("three racers expected");
+        };
+        // Alone in the maze as far as the mice are concerned
+        for racer in [first, second] {
+            let result = &racer.result;
+            assert_eq!((result.termination, result.frames), (Termination::Goal, 53));
+            assert_eq!(result.stats.collisions, 1);
+        }
+        assert_eq!(parked.result.termination, Termination::MaxFrames);
+        assert!(parked.result.score.as_ref().unwrap().disqualified);
+        assert_eq!(parked.result.stats.collisions, 0);
+        let bumping = race(&Config {
+            mouse_collisions: true,
+            ..config
+        });
+        // The bots start on top of each other and stay there, both hit the
+        // parked mouse
+        assert!(bumping.racers[2].result.stats.collisions > 0);
+        assert!(bumping.racers[0].result.stats.collisions > 1);
+    }
+
+    #[test]
+    fn race_winner_has_the_best_score() {
+        let racer = |name: &str, frames, goal| {
+            let mut state = SimulationState::new();
+            state.frame = frames;
+            state.goal = goal;
+            let termination = if goal {
+                Termination::Goal
+            } else {
+                Termination::MaxFrames
+            };
+            RacerResult {
+                name: name.to_string(),
+                result: RunResult::new(&state, termination).with_score(&ScoreConfig::default()),
+            }
+        };
+        let winner = |racers| RaceResult::new(racers).winner;
+        let race = vec![racer("slow", 80, true), racer("fast", 60, true)];
+        assert_eq!(winner(race).as_deref(), Some("fast"));
+        // Fewer frames without the goal are disqualified
+        let race = vec![racer("lost", 40, false), racer("slow", 80, true)];
+        assert_eq!(winner(race).as_deref(), Some("slow"));
+        // A tie goes to the racer listed first
+        let race = vec![racer("first", 60, true), racer("second", 60, true)];
+        assert_eq!(winner(race).as_deref(), Some("first"));
+        assert_eq!(winner(vec![racer("lost", 40, false)]), None);
+    }
+
+    #[test]
+    fn reference_bot_reaches_the_goal() {
+        let physics = PhysicsProfile::default();
+        // A bot that gets lost ends here instead of running forever
+        let limits = Limits::default().with_max_frames(5000);
+        for kind in MazeKind::all() {
+            for seed in ["a", "b", "c"] {
+                let spec = MazeSpec::from_string(&seed.to_string(), kind);
+                let mut bot = ReferenceBot::new(&physics);
+                let result = run_headless(&maze_from_spec(&spec), &physics, &mut bot, &limits);
+                assert_eq!(result.termination, Termination::Goal, "maze {spec}");
+            }
+        }
+    }
+
+    #[test]
+    fn keyboard_ramps_like_a_stick() {
+        let hold = |keys: Keys, frames: usize| {
+            let mut keyboard = Keyboard::default();
+            (0..frames).map(|_| keyboard.input(keys)).last().unwrap()
+        };
+        let right_up = Keys {
+            up: true,
+            right: true,
+            ..Keys::default()
+        };
+        let (steering, acceleration) = hold(right_up, 1);
+        assert!((steering - 0.15).abs() < 1e-6 && (acceleration - 0.15).abs() < 1e-6);
+        assert!(hold(right_up, 6).0 < 1.0);
+        assert_eq!(hold(right_up, 7), (1.0, 1.0));
+        assert_eq!(hold(right_up, 100), (1.0, 1.0));
+
+        // Releasing returns twice as fast
+        let (steering, acceleration) = keyboard_ramp((1.0, -1.0), Keys::default());
+        assert!((steering - 0.7).abs() < 1e-6 && (acceleration + 0.7).abs() < 1e-6);
+        let released = (0..4).fold((1.0, -1.0), |previous, _| {
+            keyboard_ramp(previous, Keys::default())
+        });
+        assert_eq!(released, (0.0, 0.0));
+
+        // Opposite keys cancel out
+        let all = Keys {
+            up: true,
+            down: true,
+            left: true,
+            right: true,
+        };
+        assert_eq!(hold(all, 10), (0.0, 0.0));
+        let (steering, acceleration) = keyboard_ramp((0.5, -0.5), all);
+        assert!((steering - 0.2).abs() < 1e-6 && (acceleration + 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn limits_check_in_order() {
+        let limits = Limits {
+            max_frames: Some(10),
+            max_collisions: Some(2),
+            max_collision_frames: Some(5),
+            timeout: Some(Duration::from_secs(60)),
+            frame_timeout: None,
+        };
+        let state = |frame, collisions, collision_frames, goal| {
+            let mut state = SimulationState::new();
+            state.frame = frame;
+            state.stats.collisions = collisions;
+            state.stats.collision_frames = collision_frames;
+            state.goal = goal;
+            state
+        };
+        let now = Instant::now();
+        let late = now.checked_sub(Duration::from_secs(61)).unwrap();
+        let running = SolverStatus::Running;
+        let timed_out = SolverStatus::TimedOut;
+        let exited = SolverStatus::Exited;
+        let cases = [
+            (state(9, 1, 4, false), running, now, None),
+            (
+                state(10, 0, 0, false),
+                running,
+                now,
+                Some(Termination::MaxFrames),
+            ),
+            // Reaching the goal on the frame limit
+            (state(10, 0, 0, true), running, now, Some(Termination::Goal)),
+            (
+                state(10, 1, 5, false),
+                running,
+                now,
+                Some(Termination::MaxCollisionFrames),
+            ),
+            (
+                state(10, 2, 5, false),
+                running,
+                now,
+                Some(Termination::MaxCollisions),
+            ),
+            (
+                state(10, 2, 5, false),
+                timed_out,
+                now,
+                Some(Termination::FrameTimeout),
+            ),
+            (
+                state(10, 2, 5, false),
+                timed_out,
+                late,
+                Some(Termination::Timeout),
+            ),
+            (
+                state(10, 2, 5, false),
+                exited,
+                late,
+                Some(Termination::SolverExited),
+            ),
+            (state(10, 2, 5, true), exited, late, Some(Termination::Goal)),
+        ];
+        for (state, solver, start, termination) in cases {
+            assert_eq!(limits.check(&state, solver, start), termination);
+        }
+        let unlimited = Limits::default();
+        assert_eq!(
+            unlimited.check(&state(1 << 40, 1 << 20, 1 << 30, false), running, late),
+            None
+        );
+    }
+
+    #[test]
+    fn goal_on_the_frame_limit_wins() {
+        let physics = PhysicsProfile::default();
+        let maze = maze_from_spec(&MazeSpec::from_string(
+            &"a".to_string(),
+            MazeKind::Backtracking,
+        ));
+        let run = |max_frames| {
+            let mut bot = ReferenceBot::new(&physics);
+            let limits = Limits::default().with_max_frames(max_frames);
+            run_headless(&maze, &physics, &mut bot, &limits)
+        };
+        let result = run(53);
+        assert_eq!((result.termination, result.frames), (Termination::Goal, 53));
+        let result = run(52);
+        assert_eq!(
+            (result.termination, result.frames),
+            (Termination::MaxFrames, 52)
+        );
+    }
+
+    #[test]
+    fn score_counts_contacts_once() {
+        let mut stats = RunStats::default();
+        for collision in [true, true, false, true] {
+            stats.update(collision, true);
+        }
+        stats.update(false, false);
+        stats.update(false, false);
+        assert_eq!(stats.collisions, 2);
+        assert_eq!(stats.collision_frames, 3);
+        assert_eq!(stats.longest_contact, 2);
+        assert_eq!(stats.off_course, 1);
+        let penalty = ScoreConfig {
+            mode: ScoreMode::Penalty,
+            ..Default::default()
+        };
+        assert_eq!(penalty.points(&stats, 50), 50.0 + 20.0 + 3.0 + 100.0);
+        assert!(!penalty.score(&stats, 50, true).disqualified);
+        let disqualify = ScoreConfig {
+            mode: ScoreMode::Disqualify,
+            ..Default::default()
+        };
+        assert!(disqualify.score(&stats, 50, true).disqualified);
+    }
+
+    #[test]
+    fn recording_skips_other_lines() {
+        let spec = MazeSpec::from_string(&"ghost".to_string(), MazeKind::Prims);
+        let maze = maze_from_spec(&spec);
+        let name = format!(
+            "matahatan-{}-recording_skips_other_lines.jsonl",
+            std::process::id()
+        );
+        let path = std::env::temp_dir().join(name);
+        let lines = [
+            r#"{"x":25,"y":25,"start_x":0,"start_y":0,"goal_x":24,"goal_y":24,"passages":[]}"#,
+            r#"{"x":0.9,"y":0.5,"angle":0.0,"collision":false,"goal":false}"#,
+            r#"{"x":1.1,"y":0.5,"angle":0.0,"collision":false,"goal":false}"#,
+            r#"{"frames":2,"goal":false,"termination":"closed"}"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let recording = Recording::load(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let recording = recording.unwrap();
+        assert_eq!(recording.frames.len(), 2);
+        assert_eq!(recording.at(5).unwrap().x, 1.1);
+        let entered = recording.entered(&maze);
+        assert_eq!(entered[0], Some(1));
+        assert_eq!(entered[1], Some(2));
+    }
+
+    #[test]
+    fn recording_keeps_its_maze() {
+        let spec = MazeSpec::from_string(&"ghost".to_string(), MazeKind::Prims).with_braid(0.5);
+        let other = MazeSpec::from_string(&"ghost".to_string(), MazeKind::Ellers);
+        let path = std::env::temp_dir().join(format!(
+            "matahatan-{}-recording_keeps_its_maze.jsonl",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let mut recording = Recording::new(&spec);
+        recording.frames.push(Output::new(&SimulationState::new()));
+        recording.save(path).unwrap();
+        let saved = Recording::load(path);
+        // The stdout of a stdio-run, its maze header has the id
+        let maze = serde_json::to_string(&maze_json(&maze_from_spec(&spec))).unwrap();
+        let output = r#"{"x":0.9,"y":0.5,"angle":0.0,"collision":false,"goal":false}"#;
+        std::fs::write(path, format!("{maze}\n{output}\n")).unwrap();
+        let stdout = Recording::load(path);
+        std::fs::remove_file(path).unwrap();
+        for recording in [saved.unwrap(), stdout.unwrap()] {
+            assert_eq!(recording.frames.len(), 1);
+            assert!(recording.maze == Some(spec.clone()));
+            assert!(recording.check(&spec).is_ok());
+            assert!(recording.check(&other).is_err());
+        }
+        assert!(Recording::default().check(&other).is_ok());
+    }
+
+    fn outputs(points: &[(f32, bool)]) -> Vec<Output> {
+        points
+            .iter()
+            .map(|&(x, collision)| Output {
+                x,
+                y: 0.5,
+                angle: 0.0,
+                collision,
+                goal: false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn svg_has_walls_trail_and_car() {
+        let spec = MazeSpec::from_string(&"svg".to_string(), MazeKind::Prims).with_size((5, 4));
+        let maze = maze_from_spec(&spec);
+        let mut scene = Scene::new(&maze, 10.0);
+        let frames = outputs(&[(0.5, false), (0.6, true), (0.7, true), (0.8, false)]);
+        scene.trail(&frames);
+        scene.car(frames.last().unwrap());
+        let svg = scene.to_svg();
+        assert!(svg.starts_with(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"60\" height=\"50\" \
+             viewBox=\"0 0 60 50\">"
+        ));
+        assert!(svg.ends_with("</svg>\n"));
+        let count = |element| svg.matches(element).count();
+        // Background and goal, a line per wall and per pair of frames and one
+        // circle for the one wall contact
+        assert_eq!(count("<rect "), 2);
+        assert_eq!(count("<line "), maze.walls().len() + 3);
+        assert_eq!(count("<circle "), 1);
+        assert_eq!(count("<polygon "), 1);
+    }
+
+    #[test]
+    fn animation_draws_frames_in_order() {
+        let maze = maze_from_spec(&MazeSpec::from_string(&"gif".to_string(), MazeKind::Prims));
+        let frames = outputs(&[
+            (0.5, false),
+            (0.6, false),
+            (0.7, true),
+            (0.8, true),
+            (0.9, false),
+        ]);
+        let numbers = |stride| {
+            let pictures = Scene::animation(&maze, &frames, 4.0, stride).unwrap();
+            pictures.map(|(frame, _)| frame).collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(1), [1, 2, 3, 4, 5]);
+        assert_eq!(numbers(2), [1, 3, 5]);
+        // The last frame is always drawn
+        assert_eq!(numbers(3), [1, 4, 5]);
+        assert_eq!(numbers(10), [1, 5]);
+        assert!(Scene::animation(&maze, &[], 4.0, 1)
+            .unwrap()
+            .next()
+            .is_none());
+        // Drawing the trail piece by piece ends with the picture of the run
+        let (_, last) = Scene::animation(&maze, &frames, 4.0, 2)
+            .unwrap()
+            .last()
+            .unwrap();
+        let mut scene = Scene::new(&maze, 4.0);
+        scene.trail(&frames);
+        scene.car(&frames[4]);
+        scene.counter(5);
+        assert!(last == scene.to_pixmap().unwrap());
+    }
+
+    #[test]
+    fn default_mapping_keeps_stick_shaping() {
+        let mapping = GamepadMapping::default();
+        assert_eq!(mapping.shape(0.1, 0.0), (0.0, 0.0));
+        let (steering, throttle) = mapping.shape(-0.5, 0.5);
+        assert!((steering - (-0.5f32).powi(7)).abs() < 1e-6);
+        assert!((throttle - 0.5f32.powi(3)).abs() < 1e-6);
+        let json = r#"{"steering":["right_x"],"throttle_curve":{"exponent":1.0}}"#;
+        let mapping: GamepadMapping = serde_json::from_str(json).unwrap();
+        assert_eq!(mapping.steering, vec![Axis::RightX]);
+        assert_eq!(mapping.shape(0.5, 0.5).1, 0.5);
+    }
+
+    #[test]
+    fn gamepad_mapping_is_checked() {
+        assert!(GamepadMapping::default().check().is_ok());
+        let json = r#"{"throttle_button":"FrontRightLowr"}"#;
+        let mapping: GamepadMapping = serde_json::from_str(json).unwrap();
+        assert!(mapping.check().is_err());
+        let json = r#"{"steering_curve":{"exponent":-1.0}}"#;
+        let mapping: GamepadMapping = serde_json::from_str(json).unwrap();
+        assert!(mapping.check().is_err());
+        let file = ConfigFile::parse("[gamepad.throttle_curve]\ndeadzone = 1.5").unwrap();
+        assert!(file.config().is_err());
+        let path = std::env::temp_dir().join(format!(
+            "matahatan-{}-gamepad_mapping_is_checked.json",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let mapping = GamepadMapping {
+            brake_button: None,
+            ..GamepadMapping::default()
+        };
+        mapping.save(path).unwrap();
+        let loaded = GamepadMapping::load(path);
+        let _ = std::fs::remove_file(path);
+        assert_eq!(loaded.unwrap(), mapping);
+    }
+
+    #[test]
+    fn solver_exit_is_reported() {
+        let maze = maze_from_spec(&MazeSpec::from_string(&"exit".to_string(), MazeKind::Prims));
+        let mut solver = ProcessSolver::spawn("exit 3").unwrap();
+        let limits = Limits::default();
+        let result = run_headless(&maze, &PhysicsProfile::default(), &mut solver, &limits);
+        assert_eq!(result.termination, Termination::SolverExited);
+        assert_eq!(result.frames, 0);
+        let exit = result.exit.unwrap();
+        assert_eq!((exit.code, exit.killed), (Some(3), false));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn timed_out_solver_is_killed_with_its_children() {
+        let maze = maze_from_spec(&MazeSpec::from_string(&"kill".to_string(), MazeKind::Prims));
+        let path = std::env::temp_dir().join(format!(
+            "matahatan-{}-timed_out_solver_is_killed_with_its_children.pid",
+            std::process::id()
+        ));
+        let command = format!("sleep 37 & echo $! > {}; wait", path.display());
+        let mut solver = ProcessSolver::spawn(&command).unwrap();
+        let limits = Limits {
+            frame_timeout: Some(Duration::from_millis(500)),
+            ..Limits::default()
+        };
+        let result = run_headless(&maze, &PhysicsProfile::default(), &mut solver, &limits);
+        assert_eq!(result.termination, Termination::FrameTimeout);
+        assert!(result.exit.unwrap().killed);
+        let pid = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        // Gone, or a zombie if nobody reaps orphans here
+        let alive = || {
+            std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+                .is_ok_and(|stat| !stat.rsplit(')').next().unwrap().trim().starts_with('Z'))
+        };
+        let start = Instant::now();
+        while alive() && start.elapsed() < Duration::from_secs(2) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!alive());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn solver_gets_result_and_is_restarted() {
+        let maze = maze_from_spec(&MazeSpec::from_string(&"end".to_string(), MazeKind::Prims));
+        let path = std::env::temp_dir().join(format!(
+            "matahatan-{}-solver_gets_result_and_is_restarted",
+            std::process::id()
+        ));
+        let (pid_path, lines_path) = (path.with_extension("pid"), path.with_extension("jsonl"));
+        let command = format!(
+            "while :; do echo '{{\"acceleration\":1,\"steering\":0}}'; done & \
+             echo $! > {}; cat >> {}",
+            pid_path.display(),
+            lines_path.display()
+        );
+        let mut solver = ProcessSolver::spawn(&command).unwrap();
+        let limits = Limits {
+            max_frames: Some(5),
+            ..Limits::default()
+        };
+        for _ in 0..2 {
+            let result = run_headless(&maze, &PhysicsProfile::default(), &mut solver, &limits);
+            assert_eq!(result.termination, Termination::MaxFrames);
+            let exit = result.exit.unwrap();
+            assert_eq!((exit.code, exit.killed), (Some(0), false));
+            // The loop would outlive the shell that exits with cat
+            let pid = std::fs::read_to_string(&pid_path).unwrap();
+            let alive = || {
+                std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+                    .is_ok_and(|stat| !stat.contains(") Z"))
+            };
+            let start = Instant::now();
+            while alive() && start.elapsed() < Duration::from_secs(2) {
+                thread::sleep(Duration::from_millis(10));
+            }
+            assert!(!alive());
+        }
+        let lines = std::fs::read_to_string(&lines_path).unwrap();
+        let _ = std::fs::remove_file(&pid_path);
+        let _ = std::fs::remove_file(&lines_path);
+        let results: Vec<RunResult> = lines
+            .lines()
+            .filter(|line| line.contains("\"termination\""))
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|result| result.frames == 5 && result.exit.is_none()));
+        assert!(lines.lines().last().unwrap().contains("\"termination\""));
+    }
+
+    #[test]
+    fn timed_out_reader_runs_again_after_restart() {
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let mut reader = stdio::InputReader::new(reader);
+        let after = |millis| Some(Instant::now() + Duration::from_millis(millis));
+        assert!(reader.input(after(50)).is_none());
+        assert_eq!(reader.status, SolverStatus::TimedOut);
+        // Too late for the run that timed out
+        writeln!(writer, "{{\"acceleration\":1,\"steering\":0}}").unwrap();
+        thread::sleep(Duration::from_millis(100));
+        reader.restart();
+        assert_eq!(reader.status, SolverStatus::Running);
+        assert!(reader.input(after(50)).is_none());
+        reader.restart();
+        writeln!(writer, "{{\"acceleration\":0.5,\"steering\":0}}").unwrap();
+        let input = reader.input(after(2000)).unwrap();
+        assert_eq!(input.acceleration, 0.5);
+        assert_eq!(reader.status, SolverStatus::Running);
+        drop(writer);
+        assert!(reader.input(after(2000)).is_none());
+        reader.restart();
+        assert_eq!(reader.status, SolverStatus::Exited);
+    }
+
+    #[test]
+    fn preferences_reopen_the_last_maze() {
+        let current =
+            MazeSpec::from_string(&"current".to_string(), MazeKind::Ellers).with_braid(0.5);
+        let saved: MatahatanAppState =
+            serde_json::from_str(r#"{"seed": "last", "kind": "prims", "size": [12, 9]}"#).unwrap();
+        let restored: MatahatanAppState =
+            serde_json::from_str(&serde_json::to_string(&saved).unwrap()).unwrap();
+        let last = MazeSpec::from_string(&"last".to_string(), MazeKind::Prims)
+            .with_size((12, 9))
+            .with_braid(0.5);
+        assert!(restored.maze(&current) == last);
+        // A maze id is the whole maze
+        let id = format!(r#"{{"seed": "{last}", "size": [30, 30]}}"#);
+        let restored: MatahatanAppState = serde_json::from_str(&id).unwrap();
+        assert!(restored.maze(&current) == last);
+        let random: MatahatanAppState = serde_json::from_str(r#"{"size": [7, 8]}"#).unwrap();
+        let random = random.maze(&current);
+        assert_eq!(
+            (random.size(), random.kind()),
+            ((7, 8), MazeKind::Backtracking)
+        );
+    }
+
+    #[test]
+    fn config_file_fills_in_defaults() {
+        let file = ConfigFile::parse(
+            r#"
+            gui = false
+            fps = 10
+            transport = "stdio"
+            racers = ["bot:reference"]
+
+            [maze]
+            seed = "contest"
+            kind = "prims"
+            size = [30, 20]
+
+            [limits]
+            frame_timeout = 0.5
+
+            [physics]
+            max_velocity = 0.3
+            "#,
+        )
+        .unwrap();
+        let config = file.config().unwrap();
+        assert!(config.stdio && !config.gui);
+        assert!(config.maze_given && !ConfigFile::default().config().unwrap().maze_given);
+        assert_eq!(config.framerate, 0.0);
+        assert_eq!(config.seed.as_deref(), Some("contest"));
+        assert!(config.kind == MazeKind::Prims);
+        assert_eq!(config.size, (30, 20));
+        assert_eq!(
+            config.limits.frame_timeout,
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(config.limits.max_frames, None);
+        assert_eq!(config.physics.max_velocity, 0.3);
+        assert_eq!(config.physics.turn_scaler, TURN_SCALER);
+        assert_eq!(config.racers, vec![Racer::Bot(Bot::Reference)]);
+        let maze = maze_from_spec(&MazeSpec::new(&config.seed, config.kind).with_size(config.size));
+        assert_eq!(maze.size, (30, 20));
+        let header = serde_json::to_value(maze_json(&maze)).unwrap();
+        assert_eq!(
+            (header["x"].as_i64(), header["y"].as_i64()),
+            (Some(30), Some(20))
+        );
+        assert!(ConfigFile::parse("speed = 2").is_err());
+        let both = ConfigFile::parse("transport = \"stdio\"\nbot = \"reference\"").unwrap();
+        assert!(both.config().is_err());
+        let huge = ConfigFile::parse("[maze]\nsize = [2, 3000]").unwrap();
+        assert!(huge.config().is_err());
+        assert!(ConfigFile::parse("[physics]\nmax_velocty = 0.2").is_err());
+        assert!(ConfigFile::parse("[score]\nmod = \"frames\"").is_err());
+        assert!(ConfigFile::parse("[gamepad.steering_curve]\nexponet = 2.0").is_err());
+        assert!(ConfigFile::parse("[preferences]\nshow_trails = false").is_err());
+    }
+
+    #[test]
+    fn maze_kind_names_round_trip() {
+        for kind in MazeKind::all() {
+            assert!(kind.to_string().parse::<MazeKind>() == Ok(kind));
+        }
+        assert!("maze".parse::<MazeKind>().is_err());
+        for kind in MazeKind::all() {
+            let random = maze_from_spec(&MazeSpec::new(&None, kind));
+            assert_eq!(random.kind, Some(kind));
+        }
+        let maze = maze_from_spec(&MazeSpec::from_string(
+            &"kind".to_string(),
+            MazeKind::Ellers,
+        ));
+        let header = serde_json::to_value(maze_json(&maze)).unwrap();
+        assert_eq!(header["kind"], "ellers");
+    }
+
+    #[test]
+    fn maz_has_closed_border() {
+        let spec = MazeSpec::from_string(&"maz".to_string(), MazeKind::Prims).with_size((4, 3));
+        let bytes = maze_from_spec(&spec).to_maz();
+        assert_eq!(bytes.len(), 12);
+        // West column, then bottom and top row
+        assert!(bytes[..3].iter().all(|cell| cell & 8 != 0));
+        assert!(bytes.iter().step_by(3).all(|cell| cell & 4 != 0));
+        assert!(bytes.iter().skip(2).step_by(3).all(|cell| cell & 1 != 0));
+        assert_eq!(spec.hash(), spec.clone().with_size((4, 3)).hash());
+        assert_ne!(spec.hash(), spec.clone().with_size((3, 4)).hash());
+    }
+    #[test]
+    fn maze_ids_round_trip() {
+        let spec = MazeSpec::random()
+            .with_size((7, 5))
+            .with_braid(0.25)
+            .with_stairs(2);
+        let id = spec.to_string();
+        assert!(id.parse::<MazeSpec>() == Ok(spec.clone()));
+        let header = serde_json::to_value(maze_json(&maze_from_spec(&spec))).unwrap();
+        assert_eq!(header["id"], id.as_str());
+        assert!(id.replacen('1', "0", 1).parse::<MazeSpec>().is_err());
+        assert!("1:prims:5x5:abc".parse::<MazeSpec>().is_err());
+        let seed = "0".repeat(64);
+        assert!(format!("1:prims:70000x70000:{seed}")
+            .parse::<MazeSpec>()
+            .is_err());
+        assert!(format!("1:backtracking:3000x3000:{seed}")
+            .parse::<MazeSpec>()
+            .is_err());
+        assert!(format!("1:prims:256x256:{seed}")
+            .parse::<MazeSpec>()
+            .is_ok());
+        assert!(format!("1:prims:5x5:{seed}:rooms=4000000000")
+            .parse::<MazeSpec>()
+            .is_err());
+        assert!(format!("1:prims:5x5:{seed}:stairs=10001")
+            .parse::<MazeSpec>()
+            .is_err());
+        assert_eq!(spec.with_size((1000, 1)).size, (MAX_MAZE_SIZE, 2));
+    }
 }
@@ -0,0 +1,95 @@
+use super::{SimulationState, SolverStatus};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+// Everything that can end a run. None means unlimited.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    pub max_frames: Option<i64>,
+    // Separate wall contacts, touching a wall for several frames counts once
+    pub max_collisions: Option<i64>,
+    pub max_collision_frames: Option<i64>,
+    // Wall-clock time for the whole run and for the solver to answer a frame
+    pub timeout: Option<Duration>,
+    pub frame_timeout: Option<Duration>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+    Goal,
+    MaxFrames,
+    MaxCollisions,
+    MaxCollisionFrames,
+    Timeout,
+    FrameTimeout,
+    SolverExited,
+    SpawnFailed,
+    Closed,
+    Restarted,
+}
+
+impl Termination {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Termination::Goal => "goal",
+            Termination::MaxFrames => "max_frames",
+            Termination::MaxCollisions => "max_collisions",
+            Termination::MaxCollisionFrames => "max_collision_frames",
+            Termination::Timeout => "timeout",
+            Termination::FrameTimeout => "frame_timeout",
+            Termination::SolverExited => "solver_exited",
+            Termination::SpawnFailed => "spawn_failed",
+            Termination::Closed => "closed",
+            Termination::Restarted => "restarted",
+        }
+    }
+}
+
+impl Limits {
+    pub fn with_max_frames(mut self, max_frames: i64) -> Self {
+        self.max_frames = Some(max_frames);
+        self
+    }
+
+    // How long the solver may take for the next input
+    pub fn deadline(&self, start: Instant) -> Option<Instant> {
+        let run = self.timeout.map(|timeout| start + timeout);
+        let frame = self.frame_timeout.map(|timeout| Instant::now() + timeout);
+        match (run, frame) {
+            (Some(run), Some(frame)) => Some(run.min(frame)),
+            (run, frame) => run.or(frame),
+        }
+    }
+
+    // Checked after every frame, the goal wins if a limit is hit in the same
+    // frame
+    pub fn check(
+        &self,
+        state: &SimulationState,
+        solver: SolverStatus,
+        start: Instant,
+    ) -> Option<Termination> {
+        let over = |limit: Option<i64>, value: i64| limit.is_some_and(|limit| value >= limit);
+        let timed_out = self
+            .timeout
+            .is_some_and(|timeout| start.elapsed() >= timeout);
+        if state.goal {
+            Some(Termination::Goal)
+        } else if solver == SolverStatus::Exited {
+            Some(Termination::SolverExited)
+        } else if timed_out {
+            Some(Termination::Timeout)
+        } else if solver == SolverStatus::TimedOut {
+            Some(Termination::FrameTimeout)
+        } else if over(self.max_collisions, state.stats.collisions) {
+            Some(Termination::MaxCollisions)
+        } else if over(self.max_collision_frames, state.stats.collision_frames) {
+            Some(Termination::MaxCollisionFrames)
+        } else if over(self.max_frames, state.frame) {
+            Some(Termination::MaxFrames)
+        } else {
+            None
+        }
+    }
+}
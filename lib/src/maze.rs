@@ -0,0 +1,330 @@
+use super::MazeKind;
+pub use maze_generator::prelude::{Coordinates, Direction};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+// maze_generator does not let us change passages after generation, so we copy
+// the generated maze into our own grid and do post-processing on that.
+#[derive(Clone)]
+pub struct Maze {
+    pub size: (i32, i32),
+    // Generator kind, part of the header for solvers
+    pub kind: Option<MazeKind>,
+    // Identity of the MazeSpec it was built from, see MazeSpec::from_str
+    pub id: Option<String>,
+    pub start: Coordinates,
+    pub goal: Coordinates,
+    passages: Vec<[bool; 4]>,
+}
+
+// A wall segment on the cell grid: vertical walls run from (x, y) to
+// (x, y + 1), horizontal walls from (x, y) to (x + 1, y).
+#[derive(Clone, Copy)]
+pub struct Wall {
+    pub x: i32,
+    pub y: i32,
+    pub vertical: bool,
+}
+
+#[derive(Clone)]
+pub struct Field {
+    pub coordinates: Coordinates,
+    passages: [bool; 4],
+}
+
+impl Field {
+    pub fn has_passage(&self, direction: &Direction) -> bool {
+        self.passages[direction_index(direction)]
+    }
+
+    pub fn passage_count(&self) -> usize {
+        self.passages.iter().filter(|p| **p).count()
+    }
+}
+
+impl Maze {
+    pub fn get_field(&self, coordinates: &Coordinates) -> Option<Field> {
+        self.index(coordinates).map(|index| Field {
+            coordinates: *coordinates,
+            passages: self.passages[index],
+        })
+    }
+
+    pub fn has_passage(&self, coordinates: &Coordinates, direction: &Direction) -> bool {
+        match self.index(coordinates) {
+            Some(index) => self.passages[index][direction_index(direction)],
+            None => false,
+        }
+    }
+
+    pub fn is_inside(&self, coordinates: &Coordinates) -> bool {
+        coordinates.x >= 0
+            && coordinates.x < self.size.0
+            && coordinates.y >= 0
+            && coordinates.y < self.size.1
+    }
+
+    // Removes the wall between coordinates and its neighbour in direction.
+    // Returns false if the neighbour is outside the maze.
+    pub fn carve(&mut self, coordinates: &Coordinates, direction: &Direction) -> bool {
+        let next = coordinates.next(direction);
+        match (self.index(coordinates), self.index(&next)) {
+            (Some(a), Some(b)) => {
+                self.passages[a][direction_index(direction)] = true;
+                self.passages[b][direction_index(&direction.opposite())] = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Every wall of the maze including the outer border, each wall listed once.
+    pub fn walls(&self) -> Vec<Wall> {
+        let mut walls = Vec::new();
+        for ix in 0..self.size.0 {
+            for iy in 0..self.size.1 {
+                let coordinates = (ix, iy).into();
+                if !self.has_passage(&coordinates, &Direction::West) {
+                    walls.push(Wall {
+                        x: ix,
+                        y: iy,
+                        vertical: true,
+                    });
+                }
+                if !self.has_passage(&coordinates, &Direction::North) {
+                    walls.push(Wall {
+                        x: ix,
+                        y: iy,
+                        vertical: false,
+                    });
+                }
+                if ix == self.size.0 - 1 {
+                    walls.push(Wall {
+                        x: ix + 1,
+                        y: iy,
+                        vertical: true,
+                    });
+                }
+                if iy == self.size.1 - 1 {
+                    walls.push(Wall {
+                        x: ix,
+                        y: iy + 1,
+                        vertical: false,
+                    });
+                }
+            }
+        }
+        walls
+    }
+
+    // Text picture, north is up, S and G mark start and goal
+    pub fn to_ascii(&self) -> String {
+        let (width, height) = self.size;
+        let mut text = String::new();
+        for iy in 0..height {
+            for ix in 0..width {
+                let open = self.has_passage(&(ix, iy).into(), &Direction::North);
+                text.push_str(if open { "+   " } else { "+---" });
+            }
+            text.push_str("+\n");
+            for ix in 0..width {
+                let open = self.has_passage(&(ix, iy).into(), &Direction::West);
+                text.push(if open { ' ' } else { '|' });
+                let mark = if (ix, iy) == (self.start.x, self.start.y) {
+                    " S "
+                } else if (ix, iy) == (self.goal.x, self.goal.y) {
+                    " G "
+                } else {
+                    "   "
+                };
+                text.push_str(mark);
+            }
+            text.push_str("|\n");
+        }
+        text.push_str(&"+---".repeat(width as usize));
+        text.push_str("+\n");
+        text
+    }
+
+    // Micromouse .maz: one byte of walls per cell (1 north, 2 east, 4 south,
+    // 8 west), column by column starting at the bottom left cell
+    pub fn to_maz(&self) -> Vec<u8> {
+        let (width, height) = self.size;
+        let mut bytes = Vec::with_capacity((width * height) as usize);
+        for ix in 0..width {
+            for iy in (0..height).rev() {
+                let coordinates = (ix, iy).into();
+                let mut walls = 0;
+                for (bit, direction) in [1, 2, 4, 8].iter().zip([
+                    Direction::North,
+                    Direction::East,
+                    Direction::South,
+                    Direction::West,
+                ]) {
+                    if !self.has_passage(&coordinates, &direction) {
+                        walls |= bit;
+                    }
+                }
+                bytes.push(walls);
+            }
+        }
+        bytes
+    }
+
+    pub fn dead_ends(&self) -> Vec<Coordinates> {
+        let mut dead_ends = Vec::new();
+        for iy in 0..self.size.1 {
+            for ix in 0..self.size.0 {
+                let coordinates = (ix, iy).into();
+                if let Some(field) = self.get_field(&coordinates) {
+                    if field.passage_count() == 1 {
+                        dead_ends.push(coordinates);
+                    }
+                }
+            }
+        }
+        dead_ends
+    }
+
+    // Removes the given fraction of dead ends by knocking down one of their
+    // walls, which adds loops and therefore alternative routes. Connecting two
+    // dead ends is preferred, so we remove as few walls as possible, unless
+    // that would remove one dead end more than asked for.
+    pub fn braid(&mut self, fraction: f32, rng: &mut impl Rng) {
+        let initial = self.dead_ends().len();
+        let count = (initial as f32 * fraction.clamp(0.0, 1.0)).round() as usize;
+        let remaining = initial - count;
+        loop {
+            let dead_ends = self.dead_ends();
+            if dead_ends.len() <= remaining {
+                break;
+            }
+            let Some(coordinates) = dead_ends.choose(rng) else {
+                break;
+            };
+            let join = dead_ends.len() - remaining >= 2;
+            let mut walls: Vec<Direction> = Direction::all()
+                .into_iter()
+                .filter(|direction| {
+                    !self.has_passage(coordinates, direction)
+                        && self.is_inside(&coordinates.next(direction))
+                })
+                .collect();
+            walls.shuffle(rng);
+            let preferred = walls.iter().find(|direction| {
+                self.get_field(&coordinates.next(direction))
+                    .is_some_and(|field| (field.passage_count() == 1) == join)
+            });
+            match preferred.or(walls.first()).copied() {
+                Some(direction) => {
+                    self.carve(coordinates, &direction);
+                }
+                None => break,
+            }
+        }
+    }
+
+    // Opens all walls inside the rectangle, the border of the room is kept.
+    pub fn carve_room(&mut self, corner: &Coordinates, width: i32, height: i32) {
+        for ix in corner.x..corner.x + width {
+            for iy in corner.y..corner.y + height {
+                let coordinates = (ix, iy).into();
+                if ix < corner.x + width - 1 {
+                    self.carve(&coordinates, &Direction::East);
+                }
+                if iy < corner.y + height - 1 {
+                    self.carve(&coordinates, &Direction::South);
+                }
+            }
+        }
+    }
+
+    // Opens a zig-zag band of 2x2 blocks going south and east (or west), so
+    // the mouse can drive through it diagonally.
+    pub fn carve_staircase(&mut self, start: &Coordinates, length: i32, east: bool) {
+        let side = if east {
+            Direction::East
+        } else {
+            Direction::West
+        };
+        for i in 0..length {
+            let step = if east { i } else { -i };
+            let coordinates: Coordinates = (start.x + step, start.y + i).into();
+            if !self.is_inside(&coordinates) {
+                break;
+            }
+            self.carve(&coordinates, &side);
+            self.carve(&coordinates, &Direction::South);
+            self.carve(&coordinates.next(&side), &Direction::South);
+            self.carve(&coordinates.next(&Direction::South), &side);
+        }
+    }
+
+    pub fn add_rooms(&mut self, count: u32, rng: &mut impl Rng) {
+        for _ in 0..count {
+            let width = rng.gen_range(2..=4).min(self.size.0);
+            let height = rng.gen_range(2..=4).min(self.size.1);
+            let x = rng.gen_range(0..=self.size.0 - width);
+            let y = rng.gen_range(0..=self.size.1 - height);
+            self.carve_room(&(x, y).into(), width, height);
+        }
+    }
+
+    pub fn add_staircases(&mut self, count: u32, rng: &mut impl Rng) {
+        if self.size.0 < 2 || self.size.1 < 2 {
+            return;
+        }
+        for _ in 0..count {
+            let length = rng.gen_range(3..=8);
+            let east = rng.gen_bool(0.5);
+            let x = rng.gen_range(0..self.size.0);
+            let y = rng.gen_range(0..self.size.1 - 1);
+            self.carve_staircase(&(x, y).into(), length, east);
+        }
+    }
+
+    fn index(&self, coordinates: &Coordinates) -> Option<usize> {
+        if self.is_inside(coordinates) {
+            Some((coordinates.y * self.size.0 + coordinates.x) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<&maze_generator::prelude::Maze> for Maze {
+    fn from(maze: &maze_generator::prelude::Maze) -> Self {
+        let mut passages = Vec::with_capacity((maze.size.0 * maze.size.1) as usize);
+        for iy in 0..maze.size.1 {
+            for ix in 0..maze.size.0 {
+                let mut passage = [false; 4];
+                let coordinates: Coordinates = (ix, iy).into();
+                if let Some(field) = maze.get_field(&coordinates) {
+                    // Ellers sometimes leaves passages leading out of the maze
+                    for direction in Direction::all() {
+                        passage[direction_index(&direction)] = field.has_passage(&direction)
+                            && maze.get_field(&coordinates.next(&direction)).is_some();
+                    }
+                }
+                passages.push(passage);
+            }
+        }
+        Maze {
+            size: maze.size,
+            kind: None,
+            id: None,
+            start: maze.start,
+            goal: maze.goal,
+            passages,
+        }
+    }
+}
+
+fn direction_index(direction: &Direction) -> usize {
+    match direction {
+        Direction::North => 0,
+        Direction::East => 1,
+        Direction::South => 2,
+        Direction::West => 3,
+    }
+}
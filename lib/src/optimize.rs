@@ -0,0 +1,164 @@
+use super::analysis::{shortest_path, turn_velocity};
+use super::bot::{BotTuning, ReferenceBot};
+use super::maze::Maze;
+use super::{run_headless, Limits, PhysicsProfile};
+use egui::{vec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+const ROUNDS: usize = 12;
+const MAX_BOUND_FRAMES: i64 = 1_000_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Optimized {
+    pub frames: i64,
+    pub tuning: BotTuning,
+}
+
+// Near lower bound of the frames to the goal: a time-optimal speed profile
+// along the shortest path in cells. The route goes through the middle of the
+// passages between the cells of the path, so corners are cut and staircases
+// are straight diagonals. At each bend the velocity is capped by the turning
+// limit (vel_scale) for the widest arc that fits the neighbouring sections, in
+// between the mouse accelerates and brakes as hard as the physics allows. The
+// size of the mouse and its starting angle are ignored. None if the goal
+// cannot be reached.
+pub fn lower_bound(maze: &Maze, physics: &PhysicsProfile) -> Option<i64> {
+    let path = shortest_path(maze)?;
+    let center = |x: i32, y: i32| vec2(x as f32 + 0.5, y as f32 + 0.5);
+    // From the middle of the start cell, the goal counts as soon as it is entered
+    let mut points = vec![center(maze.start.x, maze.start.y)];
+    for step in path.windows(2) {
+        let passage = (center(step[0].x, step[0].y) + center(step[1].x, step[1].y)) / 2.0;
+        let last = points.len() - 1;
+        if last > 0 && straight(points[last - 1], points[last], passage) {
+            points[last] = passage;
+        } else {
+            points.push(passage);
+        }
+    }
+    // Distance along the route and highest velocity of each bend
+    let mut bends = Vec::new();
+    let mut distance = 0.0;
+    for corner in points.windows(3) {
+        let (before, after) = (corner[1] - corner[0], corner[2] - corner[1]);
+        distance += before.length();
+        let angle = before
+            .normalized()
+            .dot(after.normalized())
+            .clamp(-1.0, 1.0)
+            .acos();
+        let radius = before.length().min(after.length()) / (angle / 2.0).tan();
+        bends.push((distance, turn_velocity(radius, physics)));
+    }
+    let total: f32 = points
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).length())
+        .sum();
+    Some(profile_frames(total, &bends, physics))
+}
+
+fn straight(a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let (before, after) = (b - a, c - b);
+    (before.x * after.y - before.y * after.x).abs() < 1e-4 && before.dot(after) > 0.0
+}
+
+// Frames for distance with full acceleration, braking just in time to pass
+// every bend at its velocity
+fn profile_frames(distance: f32, bends: &[(f32, f32)], physics: &PhysicsProfile) -> i64 {
+    let max_velocity = physics.max_velocity;
+    let brake = physics.acceleration_scaler * physics.brake_scaler;
+    // Bends further away do not need braking yet
+    let horizon = max_velocity * max_velocity / (2.0 * brake) + max_velocity;
+    let (mut position, mut velocity, mut frames) = (0.0, 0.0, 0);
+    let mut next = 0;
+    while position < distance && frames < MAX_BOUND_FRAMES {
+        while next < bends.len() && bends[next].0 <= position {
+            next += 1;
+        }
+        let mut allowed = (velocity + physics.acceleration_scaler).min(max_velocity);
+        for (at, bend_velocity) in &bends[next..] {
+            let remaining = at - position;
+            if remaining > horizon {
+                break;
+            }
+            allowed = allowed.min((bend_velocity * bend_velocity + 2.0 * brake * remaining).sqrt());
+        }
+        velocity = allowed.max(velocity - brake);
+        position += velocity;
+        frames += 1;
+    }
+    frames
+}
+
+// Searches the steering/acceleration sequences the reference controller can
+// produce (lookahead, turn distance, corner velocity, steering gain) with
+// coordinate descent on the real physics. The best run is the reference bot
+// tuned for this maze, an upper bound next to lower_bound. None if no tuning
+// tried reaches the goal.
+pub fn optimize(maze: &Maze, physics: &PhysicsProfile) -> Option<Optimized> {
+    let mut tuning = BotTuning::default();
+    let mut best = evaluate(maze, physics, &tuning, i64::MAX);
+    let mut steps = [0.4, 0.2, 0.2, 0.2];
+    for _ in 0..ROUNDS {
+        let mut improved = false;
+        for (parameter, step) in steps.iter().enumerate() {
+            for sign in [1.0, -1.0] {
+                let mut candidate = tuning.clone();
+                let value = parameter_mut(&mut candidate, parameter);
+                *value = (*value + sign * step).max(0.0);
+                if candidate == tuning {
+                    continue;
+                }
+                // Runs slower than the best are aborted early
+                let limit = best.unwrap_or(i64::MAX);
+                if let Some(frames) = evaluate(maze, physics, &candidate, limit) {
+                    if frames < limit {
+                        best = Some(frames);
+                        tuning = candidate;
+                        improved = true;
+                    }
+                }
+            }
+        }
+        if !improved {
+            for step in steps.iter_mut() {
+                *step /= 2.0;
+            }
+        }
+    }
+    Some(Optimized {
+        frames: best?,
+        tuning,
+    })
+}
+// Frames to the goal, None if the goal is not reached within max_frames
+fn evaluate(
+    maze: &Maze,
+    physics: &PhysicsProfile,
+    tuning: &BotTuning,
+    max_frames: i64,
+) -> Option<i64> {
+    let mut bot = ReferenceBot::with_tuning(physics, tuning);
+    let limit = max_frames.min(maze_frame_limit(maze));
+    let limits = Limits::default().with_max_frames(limit);
+    let result = run_headless(maze, physics, &mut bot, &limits);
+    if result.goal {
+        Some(result.frames)
+    } else {
+        None
+    }
+}
+
+// Even crawling through every cell should not take longer
+fn maze_frame_limit(maze: &Maze) -> i64 {
+    (maze.size.0 * maze.size.1) as i64 * 100
+}
+
+fn parameter_mut(tuning: &mut BotTuning, parameter: usize) -> &mut f32 {
+    match parameter {
+        0 => &mut tuning.lookahead,
+        1 => &mut tuning.turn_distance,
+        2 => &mut tuning.corner_factor,
+        _ => &mut tuning.steering_gain,
+    }
+}
@@ -0,0 +1,233 @@
+use super::maze::Maze;
+use super::stdio::{maze_json, Input, InputReader, Output};
+use super::{RunResult, Solver, SolverStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Lines kept of a solver's stderr
+const LOG_LINES: usize = 1000;
+
+// Time a solver gets to exit after its stdin was closed before it is killed
+const EXIT_GRACE: Duration = Duration::from_millis(500);
+
+// The last lines a solver wrote to stderr, shared with the GUI
+#[derive(Clone, Default)]
+pub struct SolverLog(Arc<Mutex<VecDeque<String>>>);
+
+impl SolverLog {
+    pub fn push(&self, line: String) {
+        let mut lines = self.0.lock().unwrap();
+        if lines.len() == LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+// How a solver process ended, killed is set if we had to kill it (timeout or
+// it did not exit when its input was closed)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SolverExit {
+    pub code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+    pub killed: bool,
+}
+
+impl SolverExit {
+    fn new(status: ExitStatus, killed: bool) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+        #[cfg(not(unix))]
+        let signal = None;
+        SolverExit {
+            code: status.code(),
+            signal,
+            killed,
+        }
+    }
+}
+
+// A solver running as child process, it speaks the stdio protocol over its
+// stdin/stdout. Reading and writing happens in threads, so a hanging solver
+// can be killed when the deadline has passed. The process ends with every run
+// and the next maze starts the command again.
+pub struct ProcessSolver {
+    command: String,
+    log: Option<SolverLog>,
+    echo: bool,
+    child: Child,
+    writer: Option<Sender<String>>,
+    reader: InputReader,
+    exit: Option<SolverExit>,
+}
+
+impl ProcessSolver {
+    // stderr is discarded (eval runs many solvers at once)
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        Self::start(command, None, false)
+    }
+
+    // stderr lines go to the log and with echo to our stderr as well
+    pub fn spawn_logged(command: &str, log: SolverLog, echo: bool) -> io::Result<Self> {
+        Self::start(command, Some(log), echo)
+    }
+
+    fn start(command_line: &str, log: Option<SolverLog>, echo: bool) -> io::Result<Self> {
+        let stderr = if log.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        };
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(command_line)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(stderr);
+        // Own process group, so a kill also reaches what the shell started
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = command.spawn()?;
+        let command = command_line.to_string();
+        let mut stdin = child.stdin.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        let stdout = child.stdout.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        if let (Some(log), Some(stderr)) = (log.clone(), child.stderr.take()) {
+            thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    if echo {
+                        eprintln!("{line}");
+                    }
+                    log.push(line);
+                }
+            });
+        }
+        // One JSON message per line
+        let (writer, lines) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            for line in lines {
+                if writeln!(stdin, "{line}")
+                    .and_then(|_| stdin.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        Ok(ProcessSolver {
+            command,
+            log,
+            echo,
+            child,
+            writer: Some(writer),
+            reader: InputReader::new(stdout),
+            exit: None,
+        })
+    }
+
+    // Kills the solver unless it exited within grace, what it left running in
+    // its process group is killed in any case
+    fn stop(&mut self, grace: Duration) {
+        if self.exit.is_some() {
+            return;
+        }
+        // Closes its stdin once the writer thread is done
+        self.writer = None;
+        let start = Instant::now();
+        let mut killed = false;
+        let status = loop {
+            match self.child.try_wait() {
+                Ok(Some(status)) => break Some(status),
+                Ok(None) if start.elapsed() < grace => thread::sleep(Duration::from_millis(10)),
+                _ => {
+                    killed = true;
+                    self.kill_group();
+                    let _ = self.child.kill();
+                    break self.child.wait().ok();
+                }
+            }
+        };
+        // The group outlives the shell while one of its processes runs
+        self.kill_group();
+        if let Some(status) = status {
+            self.exit = Some(SolverExit::new(status, killed));
+        }
+    }
+
+    // sh -c may have started the solver as its child
+    fn kill_group(&self) {
+        #[cfg(unix)]
+        if let Ok(pid) = libc::pid_t::try_from(self.child.id()) {
+            unsafe {
+                libc::kill(-pid, libc::SIGKILL);
+            }
+        }
+    }
+
+    fn send<T: Serialize>(&mut self, value: &T) {
+        if let (Some(writer), Ok(line)) = (&self.writer, serde_json::to_string(value)) {
+            if writer.send(line).is_err() {
+                self.reader.status = SolverStatus::Exited;
+                self.stop(Duration::ZERO);
+            }
+        }
+    }
+}
+
+impl Solver for ProcessSolver {
+    fn maze(&mut self, maze: &Maze) {
+        if self.exit.is_some() {
+            match Self::start(&self.command, self.log.clone(), self.echo) {
+                Ok(solver) => *self = solver,
+                Err(error) => {
+                    eprintln!("failed to restart solver: {error}");
+                    self.reader.status = SolverStatus::Exited;
+                    return;
+                }
+            }
+        }
+        self.send(&maze_json(maze));
+    }
+
+    fn input(&mut self, deadline: Option<Instant>) -> Option<Input> {
+        let input = self.reader.input(deadline);
+        match self.reader.status {
+            SolverStatus::Running => (),
+            SolverStatus::TimedOut => self.stop(Duration::ZERO),
+            SolverStatus::Exited => self.stop(EXIT_GRACE),
+        }
+        input
+    }
+
+    fn output(&mut self, output: &Output) {
+        self.send(output);
+    }
+
+    fn status(&self) -> SolverStatus {
+        self.reader.status
+    }
+
+    // The result is the last line it gets before its input is closed
+    fn finish(&mut self, result: &RunResult) -> Option<SolverExit> {
+        self.send(result);
+        self.stop(EXIT_GRACE);
+        self.exit.clone()
+    }
+}
+
+impl Drop for ProcessSolver {
+    fn drop(&mut self) {
+        self.stop(Duration::ZERO);
+    }
+}
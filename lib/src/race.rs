@@ -0,0 +1,153 @@
+use super::maze::Maze;
+use super::process::{ProcessSolver, SolverExit, SolverLog};
+use super::replay::cell_index;
+use super::tcp::TcpSolver;
+use super::{Bot, PhysicsProfile, ReferenceBot, RunResult, SimulationState, Solver, Termination};
+use serde::Serialize;
+use std::io;
+
+// Where an additional mouse gets its input from
+#[derive(Clone, Debug, PartialEq)]
+pub enum Racer {
+    Bot(Bot),
+    Process(String),
+    Tcp(String),
+    // Id of a controller as shown in the calibration, --stick drives with 0
+    Stick(u8),
+}
+
+impl Racer {
+    // bot:reference, tcp:ADDRESS, stick:ID, cmd:COMMAND or just a command
+    pub fn parse(spec: &str) -> Option<Self> {
+        if let Some(id) = spec.strip_prefix("stick:") {
+            id.parse().ok().map(Racer::Stick)
+        } else if let Some(bot) = spec.strip_prefix("bot:") {
+            match bot {
+                "reference" => Some(Racer::Bot(Bot::Reference)),
+                _ => None,
+            }
+        } else if let Some(address) = spec.strip_prefix("tcp:") {
+            Some(Racer::Tcp(address.to_string()))
+        } else {
+            let command = spec.strip_prefix("cmd:").unwrap_or(spec);
+            Some(Racer::Process(command.to_string()))
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Racer::Bot(Bot::Reference) => "reference".to_string(),
+            Racer::Process(command) => command.clone(),
+            Racer::Tcp(address) => format!("tcp:{address}"),
+            Racer::Stick(id) => format!("stick:{id}"),
+        }
+    }
+
+    // A process writes its stderr to log, echoed without a GUI
+    pub fn solver(
+        &self,
+        physics: &PhysicsProfile,
+        log: &SolverLog,
+        echo: bool,
+    ) -> io::Result<Box<dyn Solver>> {
+        Ok(match self {
+            Racer::Bot(Bot::Reference) => Box::new(ReferenceBot::new(physics)),
+            Racer::Process(command) => {
+                Box::new(ProcessSolver::spawn_logged(command, log.clone(), echo)?)
+            }
+            Racer::Tcp(address) => Box::new(TcpSolver::accept(address)?),
+            Racer::Stick(_) => return Err(io::Error::other("a stick is not a solver")),
+        })
+    }
+}
+
+// One mouse in the maze, it stops when it has a termination
+#[derive(Clone)]
+pub struct Mouse {
+    pub name: String,
+    pub simulation: SimulationState,
+    pub termination: Option<Termination>,
+    // Frame in which each cell was entered first, indexed like the maze
+    pub entered: Vec<Option<i64>>,
+    // Set when a solver process has ended
+    pub exit: Option<SolverExit>,
+    // stderr of a solver process, kept across restarts
+    pub log: SolverLog,
+}
+
+impl Mouse {
+    pub fn new(name: String, maze: &Maze) -> Self {
+        Mouse {
+            name,
+            simulation: SimulationState::new(),
+            termination: None,
+            entered: vec![None; (maze.size.0 * maze.size.1) as usize],
+            exit: None,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn enter(&mut self, maze: &Maze) {
+        let position = self.simulation.position;
+        if let Some(cell) = cell_index(maze, position.x, position.y) {
+            self.entered[cell].get_or_insert(self.simulation.frame);
+        }
+    }
+
+    pub fn running(&self) -> bool {
+        self.termination.is_none()
+    }
+}
+
+// Mice are balls of the same radius, overlapping mice are pushed apart and
+// both lose velocity like on a wall
+pub fn mouse_collisions(mice: &mut [Mouse], radius: f32) {
+    for a in 0..mice.len() {
+        for b in (a + 1)..mice.len() {
+            if !mice[a].running() || !mice[b].running() {
+                continue;
+            }
+            let distance = mice[b].simulation.position - mice[a].simulation.position;
+            let length = distance.length();
+            if length >= 2.0 * radius || length == 0.0 {
+                continue;
+            }
+            let push = distance / length * (2.0 * radius - length) / 2.0;
+            for (index, push) in [(a, -push), (b, push)] {
+                let state = &mut mice[index].simulation;
+                state.position += push;
+                state.collision = true;
+                state.velocity -= state.velocity * 0.2 + 0.001;
+                state.velocity = state.velocity.max(0.0);
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RacerResult {
+    pub name: String,
+    #[serde(flatten)]
+    pub result: RunResult,
+}
+
+// The winner has the best score among the racers not disqualified, on a tie
+// the one listed first
+#[derive(Serialize, Debug, Clone)]
+pub struct RaceResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub winner: Option<String>,
+    pub racers: Vec<RacerResult>,
+}
+
+impl RaceResult {
+    pub fn new(racers: Vec<RacerResult>) -> Self {
+        let winner = racers
+            .iter()
+            .filter_map(|racer| racer.result.score.as_ref().map(|score| (racer, score)))
+            .filter(|(_, score)| !score.disqualified)
+            .min_by(|(_, a), (_, b)| a.points.total_cmp(&b.points))
+            .map(|(racer, _)| racer.name.clone());
+        RaceResult { winner, racers }
+    }
+}
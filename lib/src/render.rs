@@ -0,0 +1,424 @@
+use super::maze::{Maze, Wall};
+use super::stdio::Output;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use tiny_skia::{FillRule, LineCap, Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+type Rgba = [u8; 4];
+
+const BACKGROUND: Rgba = [27, 27, 27, 255];
+const WALL: Rgba = [255, 255, 255, 255];
+const GOAL: Rgba = [0, 100, 0, 255];
+const TRAIL: Rgba = [255, 255, 224, 200];
+const COLLISION: Rgba = [255, 0, 0, 255];
+const CAR: Rgba = [255, 255, 224, 255];
+const COUNTER: Rgba = [200, 200, 200, 255];
+
+// Largest side of a raster picture, larger ones are refused instead of
+// failing to allocate
+pub const MAX_PIXELS: u32 = 16384;
+
+// 3x5 pixel digits for the frame counter, one row per entry
+const DIGITS: [[u8; 5]; 10] = [
+    [7, 5, 5, 5, 7],
+    [2, 6, 2, 2, 7],
+    [7, 1, 7, 4, 7],
+    [7, 1, 7, 1, 7],
+    [5, 5, 7, 1, 1],
+    [7, 4, 7, 1, 7],
+    [7, 4, 7, 5, 7],
+    [7, 1, 1, 1, 1],
+    [7, 5, 7, 5, 7],
+    [7, 5, 7, 1, 7],
+];
+
+// Drawn the same way to SVG and PNG, coordinates in pixels
+#[derive(Clone)]
+enum Shape {
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: Rgba,
+    },
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: Rgba,
+    },
+    Circle {
+        x: f32,
+        y: f32,
+        radius: f32,
+        width: f32,
+        color: Rgba,
+    },
+    Polygon {
+        points: Vec<(f32, f32)>,
+        color: Rgba,
+    },
+}
+
+// A maze picture without a window, same geometry as the GUI: the walls of
+// the collision world, the goal cell and the car triangle
+#[derive(Clone)]
+pub struct Scene {
+    width: u32,
+    height: u32,
+    // Pixels per cell, half a cell of margin around the maze
+    cell: f32,
+    shapes: Vec<Shape>,
+}
+
+impl Scene {
+    pub fn new(maze: &Maze, cell: f32) -> Self {
+        let cell = cell.max(1.0);
+        let mut scene = Scene {
+            width: ((maze.size.0 + 1) as f32 * cell).ceil() as u32,
+            height: ((maze.size.1 + 1) as f32 * cell).ceil() as u32,
+            cell,
+            shapes: Vec::new(),
+        };
+        scene.shapes.push(Shape::Rect {
+            x: 0.0,
+            y: 0.0,
+            width: scene.width as f32,
+            height: scene.height as f32,
+            color: BACKGROUND,
+        });
+        let (x, y) = scene.point(maze.goal.x as f32, maze.goal.y as f32);
+        scene.shapes.push(Shape::Rect {
+            x,
+            y,
+            width: cell,
+            height: cell,
+            color: GOAL,
+        });
+        for wall in maze.walls() {
+            scene.wall(&wall);
+        }
+        scene
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        ((x + 0.5) * self.cell, (y + 0.5) * self.cell)
+    }
+
+    fn wall(&mut self, wall: &Wall) {
+        let (ex, ey) = if wall.vertical { (0, 1) } else { (1, 0) };
+        self.shapes.push(Shape::Line {
+            from: self.point(wall.x as f32, wall.y as f32),
+            to: self.point((wall.x + ex) as f32, (wall.y + ey) as f32),
+            width: (self.cell * 0.06).max(1.0),
+            color: WALL,
+        });
+    }
+
+    // Path of the frames, circles where a wall contact starts
+    pub fn trail(&mut self, frames: &[Output]) {
+        let width = (self.cell * 0.08).max(1.0);
+        for pair in frames.windows(2) {
+            let from = self.point(pair[0].x, pair[0].y);
+            let to = self.point(pair[1].x, pair[1].y);
+            self.shapes.push(Shape::Line {
+                from,
+                to,
+                width,
+                color: TRAIL,
+            });
+            if pair[1].collision && !pair[0].collision {
+                self.shapes.push(Shape::Circle {
+                    x: to.0,
+                    y: to.1,
+                    radius: (self.cell * 0.12).max(2.0),
+                    width,
+                    color: COLLISION,
+                });
+            }
+        }
+    }
+
+    pub fn car(&mut self, output: &Output) {
+        let (sin, cos) = output.angle.sin_cos();
+        let (x, y) = self.point(output.x, output.y);
+        let points = [(0.6, 0.0), (0.0, 0.15), (0.0, -0.15)]
+            .iter()
+            .map(|(vx, vy)| {
+                let (vx, vy) = (vx * self.cell, vy * self.cell);
+                (x + vx * cos - vy * sin, y + vx * sin + vy * cos)
+            })
+            .collect();
+        self.shapes.push(Shape::Polygon { points, color: CAR });
+    }
+
+    // Frame number in the top margin
+    pub fn counter(&mut self, frame: i64) {
+        let pixel = (self.cell * 0.08).max(1.0);
+        let mut x = self.cell * 0.5;
+        for digit in frame.to_string().bytes().map(|b| (b - b'0') as usize) {
+            for (row, bits) in DIGITS[digit].iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (4 >> column) != 0 {
+                        self.shapes.push(Shape::Rect {
+                            x: x + column as f32 * pixel,
+                            y: pixel + row as f32 * pixel,
+                            width: pixel,
+                            height: pixel,
+                            color: COUNTER,
+                        });
+                    }
+                }
+            }
+            x += 4.0 * pixel;
+        }
+    }
+
+    // Every stride-th frame (and the last) with the trail so far, the car and
+    // the frame counter. The trail is drawn once onto a picture that grows
+    // with the frames, only car and counter are drawn per picture.
+    pub fn animation<'a>(
+        maze: &Maze,
+        frames: &'a [Output],
+        cell: f32,
+        stride: usize,
+    ) -> io::Result<impl Iterator<Item = (i64, Pixmap)> + 'a> {
+        let base = Scene::new(maze, cell);
+        let mut pixmap = base.to_pixmap()?;
+        let stride = stride.max(1);
+        let mut ends: Vec<usize> = (1..=frames.len()).step_by(stride).collect();
+        if ends.last() != Some(&frames.len()) && !frames.is_empty() {
+            ends.push(frames.len());
+        }
+        let mut drawn = 1;
+        Ok(ends.into_iter().map(move |end| {
+            let mut trail = base.overlay();
+            trail.trail(&frames[drawn - 1..end]);
+            trail.draw(&mut pixmap);
+            drawn = end;
+            let mut picture = pixmap.clone();
+            let mut overlay = base.overlay();
+            overlay.car(&frames[end - 1]);
+            overlay.counter(end as i64);
+            overlay.draw(&mut picture);
+            (end as i64, picture)
+        }))
+    }
+
+    // Same size and cell, nothing drawn
+    fn overlay(&self) -> Scene {
+        Scene {
+            width: self.width,
+            height: self.height,
+            cell: self.cell,
+            shapes: Vec::new(),
+        }
+    }
+
+    pub fn to_svg(&self) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+             viewBox=\"0 0 {0} {1}\">\n",
+            self.width, self.height
+        );
+        for shape in &self.shapes {
+            let _ = match shape {
+                Shape::Line {
+                    from,
+                    to,
+                    width,
+                    color,
+                } => writeln!(
+                    svg,
+                    "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke-width=\"{width}\" \
+                     stroke-linecap=\"round\" {}/>",
+                    from.0,
+                    from.1,
+                    to.0,
+                    to.1,
+                    svg_color("stroke", color)
+                ),
+                Shape::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                } => writeln!(
+                    svg,
+                    "<rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" {}/>",
+                    svg_color("fill", color)
+                ),
+                Shape::Circle {
+                    x,
+                    y,
+                    radius,
+                    width,
+                    color,
+                } => writeln!(
+                    svg,
+                    "<circle cx=\"{x}\" cy=\"{y}\" r=\"{radius}\" fill=\"none\" \
+                     stroke-width=\"{width}\" {}/>",
+                    svg_color("stroke", color)
+                ),
+                Shape::Polygon { points, color } => {
+                    let points: Vec<String> =
+                        points.iter().map(|(x, y)| format!("{x},{y}")).collect();
+                    writeln!(
+                        svg,
+                        "<polygon points=\"{}\" {}/>",
+                        points.join(" "),
+                        svg_color("fill", color)
+                    )
+                }
+            };
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    pub fn to_pixmap(&self) -> io::Result<Pixmap> {
+        let (width, height) = (self.width, self.height);
+        if width > MAX_PIXELS || height > MAX_PIXELS {
+            return Err(io::Error::other(format!(
+                "picture of {width}x{height} pixels, at most {MAX_PIXELS} per side"
+            )));
+        }
+        let mut pixmap = Pixmap::new(width.max(1), height.max(1))
+            .ok_or_else(|| io::Error::other(format!("cannot draw {width}x{height} pixels")))?;
+        self.draw(&mut pixmap);
+        Ok(pixmap)
+    }
+
+    fn draw(&self, pixmap: &mut Pixmap) {
+        for shape in &self.shapes {
+            let (path, color, stroke) = match shape {
+                Shape::Line {
+                    from,
+                    to,
+                    width,
+                    color,
+                } => {
+                    let mut builder = PathBuilder::new();
+                    builder.move_to(from.0, from.1);
+                    builder.line_to(to.0, to.1);
+                    (builder.finish(), color, Some(*width))
+                }
+                Shape::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                } => {
+                    let rect = tiny_skia::Rect::from_xywh(*x, *y, *width, *height);
+                    (rect.map(PathBuilder::from_rect), color, None)
+                }
+                Shape::Circle {
+                    x,
+                    y,
+                    radius,
+                    width,
+                    color,
+                } => (
+                    PathBuilder::from_circle(*x, *y, *radius),
+                    color,
+                    Some(*width),
+                ),
+                Shape::Polygon { points, color } => {
+                    let mut builder = PathBuilder::new();
+                    for (index, (x, y)) in points.iter().enumerate() {
+                        if index == 0 {
+                            builder.move_to(*x, *y);
+                        } else {
+                            builder.line_to(*x, *y);
+                        }
+                    }
+                    builder.close();
+                    (builder.finish(), color, None)
+                }
+            };
+            let Some(path) = path else { continue };
+            let mut paint = Paint::default();
+            paint.set_color_rgba8(color[0], color[1], color[2], color[3]);
+            paint.anti_alias = true;
+            match stroke {
+                Some(width) => {
+                    let stroke = Stroke {
+                        width,
+                        line_cap: LineCap::Round,
+                        ..Stroke::default()
+                    };
+                    pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+                }
+                None => {
+                    pixmap.fill_path(
+                        &path,
+                        &paint,
+                        FillRule::Winding,
+                        Transform::identity(),
+                        None,
+                    );
+                }
+            }
+        }
+    }
+
+    // SVG for a .svg path, PNG otherwise
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        if path.to_lowercase().ends_with(".svg") {
+            fs::write(path, self.to_svg())
+        } else {
+            save_png(path, &self.to_pixmap()?)
+        }
+    }
+}
+
+fn svg_color(attribute: &str, color: &Rgba) -> String {
+    let [r, g, b, a] = color;
+    let mut svg = format!("{attribute}=\"#{r:02x}{g:02x}{b:02x}\"");
+    if *a < 255 {
+        let _ = write!(svg, " {attribute}-opacity=\"{:.3}\"", *a as f32 / 255.0);
+    }
+    svg
+}
+
+pub fn save_png(path: &str, pixmap: &Pixmap) -> io::Result<()> {
+    fs::write(path, pixmap.encode_png().map_err(io::Error::other)?)
+}
+
+// Animated GIF, delay between the pictures in 1/100 s, loops forever
+pub fn save_gif(path: &str, pictures: impl Iterator<Item = Pixmap>, delay: u16) -> io::Result<()> {
+    let mut pictures = pictures.peekable();
+    let Some(first) = pictures.peek() else {
+        return Err(io::Error::other("no frames to animate"));
+    };
+    // GIF sizes are 16 bit
+    let (width, height) = (first.width(), first.height());
+    let too_large = || io::Error::other(format!("GIF of {width}x{height} pixels is too large"));
+    let width = u16::try_from(width).map_err(|_| too_large())?;
+    let height = u16::try_from(height).map_err(|_| too_large())?;
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = gif::Encoder::new(writer, width, height, &[]).map_err(io::Error::other)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(io::Error::other)?;
+    for picture in pictures {
+        // The background is opaque, so the premultiplied pixels are plain RGBA
+        let mut pixels = picture.take();
+        let mut frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
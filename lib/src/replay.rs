@@ -0,0 +1,104 @@
+use super::maze::{Coordinates, Maze};
+use super::stdio::Output;
+use super::MazeSpec;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+// A recorded run: the id of its maze, then the output of every frame, one
+// JSON line per frame as a stdio-solver gets it. Other lines (result) are
+// skipped when loading, so the stdout of a stdio-run can be used as well, its
+// maze header has the id.
+#[derive(Clone, Default)]
+pub struct Recording {
+    // None for recordings without id
+    pub maze: Option<MazeSpec>,
+    pub frames: Vec<Output>,
+}
+
+// First line of a recording, the maze header of stdio has more fields
+#[derive(Serialize, Deserialize)]
+struct Header {
+    id: String,
+}
+
+impl Recording {
+    pub fn new(maze: &MazeSpec) -> Self {
+        Recording {
+            maze: Some(maze.clone()),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let mut recording = Recording::default();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if let Ok(output) = serde_json::from_str::<Output>(&line) {
+                recording.frames.push(output);
+            } else if let Ok(header) = serde_json::from_str::<Header>(&line) {
+                // The first maze, a stdio-run that was restarted has more
+                if recording.maze.is_none() {
+                    let maze = header.id.parse().map_err(|error| {
+                        io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {error}"))
+                    })?;
+                    recording.maze = Some(maze);
+                }
+            }
+        }
+        Ok(recording)
+    }
+
+    // A recording of another maze would be drawn over the wrong walls
+    pub fn check(&self, maze: &MazeSpec) -> io::Result<()> {
+        match &self.maze {
+            Some(recorded) if recorded != maze => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("recorded in maze {recorded}, not in {maze}"),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if let Some(maze) = &self.maze {
+            let header = Header {
+                id: maze.to_string(),
+            };
+            serde_json::to_writer(&mut writer, &header)?;
+            writeln!(writer)?;
+        }
+        for output in &self.frames {
+            serde_json::to_writer(&mut writer, output)?;
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+
+    // Output of a frame (the first frame is 1), stays at the end
+    pub fn at(&self, frame: i64) -> Option<&Output> {
+        let index = (frame.max(1) - 1) as usize;
+        self.frames.get(index).or(self.frames.last())
+    }
+
+    // Frame in which each cell was entered first, indexed like the maze
+    pub fn entered(&self, maze: &Maze) -> Vec<Option<i64>> {
+        let mut entered = vec![None; (maze.size.0 * maze.size.1) as usize];
+        for (index, output) in self.frames.iter().enumerate() {
+            if let Some(cell) = cell_index(maze, output.x, output.y) {
+                entered[cell].get_or_insert(index as i64 + 1);
+            }
+        }
+        entered
+    }
+}
+
+pub fn cell_index(maze: &Maze, x: f32, y: f32) -> Option<usize> {
+    let cell: Coordinates = (x.floor() as i32, y.floor() as i32).into();
+    if maze.is_inside(&cell) {
+        Some((cell.y * maze.size.0 + cell.x) as usize)
+    } else {
+        None
+    }
+}
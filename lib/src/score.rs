@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+// Counted every frame, the score is computed from it at the end of the run.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RunStats {
+    // Separate wall contacts and frames in which the mouse touched a wall
+    pub collisions: i64,
+    pub collision_frames: i64,
+    // Longest wall contact in frames (scraping along a wall)
+    pub longest_contact: i64,
+    // Times the mouse left the maze (collision bugs)
+    pub off_course: i64,
+    #[serde(skip)]
+    contact: i64,
+    #[serde(skip)]
+    outside: bool,
+}
+
+impl RunStats {
+    pub fn update(&mut self, collision: bool, inside: bool) {
+        if collision {
+            if self.contact == 0 {
+                self.collisions += 1;
+            }
+            self.contact += 1;
+            self.collision_frames += 1;
+            self.longest_contact = self.longest_contact.max(self.contact);
+        } else {
+            self.contact = 0;
+        }
+        if !inside && !self.outside {
+            self.off_course += 1;
+        }
+        self.outside = !inside;
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreMode {
+    // Least frames wins, collisions only slow you down
+    Frames,
+    // Frames plus penalty frames for collisions and leaving the maze
+    Penalty,
+    // Frames, but any collision or leaving the maze disqualifies
+    Disqualify,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoreConfig {
+    pub mode: ScoreMode,
+    // Penalty frames per wall contact, per frame touching a wall and per time
+    // leaving the maze
+    pub collision_penalty: f32,
+    pub contact_penalty: f32,
+    pub off_course_penalty: f32,
+}
+
+impl Default for ScoreConfig {
+    fn default() -> Self {
+        ScoreConfig {
+            mode: ScoreMode::Frames,
+            collision_penalty: 10.0,
+            contact_penalty: 1.0,
+            off_course_penalty: 100.0,
+        }
+    }
+}
+
+// Lower is better, a run that does not reach the goal is always disqualified
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Score {
+    pub mode: ScoreMode,
+    pub points: f32,
+    pub disqualified: bool,
+}
+
+impl ScoreConfig {
+    pub fn points(&self, stats: &RunStats, frames: i64) -> f32 {
+        let frames = frames as f32;
+        match self.mode {
+            ScoreMode::Frames | ScoreMode::Disqualify => frames,
+            ScoreMode::Penalty => {
+                frames
+                    + stats.collisions as f32 * self.collision_penalty
+                    + stats.collision_frames as f32 * self.contact_penalty
+                    + stats.off_course as f32 * self.off_course_penalty
+            }
+        }
+    }
+
+    pub fn crashed(&self, stats: &RunStats) -> bool {
+        self.mode == ScoreMode::Disqualify && (stats.collisions > 0 || stats.off_course > 0)
+    }
+
+    pub fn score(&self, stats: &RunStats, frames: i64, goal: bool) -> Score {
+        Score {
+            mode: self.mode,
+            points: self.points(stats, frames),
+            disqualified: !goal || self.crashed(stats),
+        }
+    }
+}
@@ -1,8 +1,10 @@
-use super::SimulationState;
-use maze_generator::prelude::*;
+use super::maze::{Direction, Maze};
+use super::{MazeKind, SimulationState, Solver, SolverStatus};
 use serde::{Deserialize, Serialize};
-use serde_json;
-use std::io;
+use std::io::{self, BufRead, BufReader, Read};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::Instant;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Input {
@@ -16,12 +18,29 @@ pub struct Output {
     pub y: f32,
     pub angle: f32,
     pub collision: bool,
+    pub goal: bool,
+}
+
+impl Output {
+    pub fn new(state: &SimulationState) -> Self {
+        Output {
+            x: state.position.x,
+            y: state.position.y,
+            angle: state.angle,
+            collision: state.collision,
+            goal: state.goal,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
-struct MazeJson {
+pub struct MazeJson {
     x: i32,
     y: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kind: Option<MazeKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     start_x: i32,
     start_y: i32,
     goal_x: i32,
@@ -29,36 +48,133 @@ struct MazeJson {
     passages: Vec<Vec<Vec<String>>>,
 }
 
-pub fn get_input() -> Option<Input> {
-    let mut input_string = String::new();
-    match io::stdin().read_line(&mut input_string) {
-        Ok(_) => match serde_json::from_str::<Input>(&input_string) {
-            Ok(input) => Some(input),
-            Err(error) => {
-                eprintln!("json error: {error}");
-                None
-            }
-        },
-        Err(_) => None,
+pub struct StdioSolver {
+    reader: InputReader,
+    // Input may come ahead of the first maze, later mazes are restarts
+    started: bool,
+}
+
+impl StdioSolver {
+    pub fn new() -> Self {
+        StdioSolver {
+            reader: InputReader::new(io::stdin()),
+            started: false,
+        }
+    }
+}
+
+impl Default for StdioSolver {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-pub fn write_output(state: &SimulationState) {
-    let output = Output {
-        x: state.position.x,
-        y: state.position.y,
-        angle: state.angle,
-        collision: state.collision,
-    };
-    match serde_json::to_string(&output) {
-        Ok(out_str) => println!("{}", out_str),
-        Err(_) => (),
+impl Solver for StdioSolver {
+    fn maze(&mut self, maze: &Maze) {
+        if self.started {
+            self.reader.restart();
+        }
+        self.started = true;
+        write_maze(maze);
+    }
+
+    fn input(&mut self, deadline: Option<Instant>) -> Option<Input> {
+        self.reader.input(deadline)
+    }
+
+    fn output(&mut self, output: &Output) {
+        write_json(output);
+    }
+
+    fn status(&self) -> SolverStatus {
+        self.reader.status
+    }
+}
+
+// Reads input lines in a thread, so waiting for them can time out. A closed
+// stream means the solver has exited.
+pub struct InputReader {
+    receiver: Receiver<Option<Input>>,
+    pub status: SolverStatus,
+}
+
+impl InputReader {
+    pub fn new<R: Read + Send + 'static>(reader: R) -> Self {
+        // Bounded, a solver writing faster than we simulate has to wait
+        let (sender, receiver) = mpsc::sync_channel(16);
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else { break };
+                let input = match serde_json::from_str::<Input>(&line) {
+                    Ok(input) => Some(input),
+                    Err(error) => {
+                        eprintln!("json error: {error}");
+                        None
+                    }
+                };
+                if sender.send(input).is_err() {
+                    break;
+                }
+            }
+        });
+        InputReader {
+            receiver,
+            status: SolverStatus::Running,
+        }
+    }
+
+    // For the next maze: a timeout only ends its run and what the solver sent
+    // for the last one is dropped
+    pub fn restart(&mut self) {
+        if self.status == SolverStatus::TimedOut {
+            self.status = SolverStatus::Running;
+        }
+        loop {
+            match self.receiver.try_recv() {
+                Ok(_) => (),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.status = SolverStatus::Exited;
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn input(&mut self, deadline: Option<Instant>) -> Option<Input> {
+        if self.status != SolverStatus::Running {
+            return None;
+        }
+        let result = match deadline {
+            Some(deadline) => self
+                .receiver
+                .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => self
+                .receiver
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match result {
+            Ok(input) => input,
+            Err(RecvTimeoutError::Timeout) => {
+                self.status = SolverStatus::TimedOut;
+                None
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                self.status = SolverStatus::Exited;
+                None
+            }
+        }
     }
 }
 
 pub fn write_maze(maze: &Maze) {
-    let x = maze.size.1;
-    let y = maze.size.0;
+    write_json(&maze_json(maze));
+}
+
+pub fn maze_json(maze: &Maze) -> MazeJson {
+    let x = maze.size.0;
+    let y = maze.size.1;
     let empty: Vec<String> = Vec::new();
     let mut passages: Vec<Vec<Vec<String>>> = vec![vec![empty; y as usize]; x as usize];
     for ix in 0..x {
@@ -80,17 +196,21 @@ pub fn write_maze(maze: &Maze) {
             }
         }
     }
-    let maze_json = MazeJson {
+    MazeJson {
         x,
         y,
+        kind: maze.kind,
+        id: maze.id.clone(),
         start_x: maze.start.x,
         start_y: maze.start.y,
         goal_x: maze.goal.x,
         goal_y: maze.goal.y,
         passages,
-    };
-    match serde_json::to_string(&maze_json) {
-        Ok(out_str) => println!("{}", out_str),
-        Err(_) => (),
+    }
+}
+
+pub fn write_json<T: Serialize>(value: &T) {
+    if let Ok(out_str) = serde_json::to_string(value) {
+        println!("{}", out_str)
     }
 }
@@ -0,0 +1,74 @@
+use super::maze::Maze;
+use super::process::SolverExit;
+use super::stdio::{maze_json, Input, InputReader, Output};
+use super::{RunResult, Solver, SolverStatus};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Instant;
+
+// A solver connecting over TCP, it speaks the stdio protocol over the
+// connection. We listen on the address and wait for one connection.
+pub struct TcpSolver {
+    stream: TcpStream,
+    reader: InputReader,
+    // Like StdioSolver
+    started: bool,
+}
+
+impl TcpSolver {
+    pub fn accept(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        eprintln!("waiting for solver on {}", listener.local_addr()?);
+        let (stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        Ok(TcpSolver {
+            reader: InputReader::new(stream.try_clone()?),
+            stream,
+            started: false,
+        })
+    }
+
+    fn send<T: Serialize>(&mut self, value: &T) {
+        let sent = serde_json::to_writer(&mut self.stream, value)
+            .map_err(io::Error::from)
+            .and_then(|_| self.stream.write_all(b"\n"));
+        if sent.is_err() {
+            self.reader.status = SolverStatus::Exited;
+        }
+    }
+}
+
+impl Solver for TcpSolver {
+    fn maze(&mut self, maze: &Maze) {
+        if self.started {
+            self.reader.restart();
+        }
+        self.started = true;
+        self.send(&maze_json(maze));
+    }
+
+    fn input(&mut self, deadline: Option<Instant>) -> Option<Input> {
+        self.reader.input(deadline)
+    }
+
+    fn output(&mut self, output: &Output) {
+        self.send(output);
+    }
+
+    fn status(&self) -> SolverStatus {
+        self.reader.status
+    }
+
+    // The connection stays open for the next maze
+    fn finish(&mut self, result: &RunResult) -> Option<SolverExit> {
+        self.send(result);
+        None
+    }
+}
+
+impl Drop for TcpSolver {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
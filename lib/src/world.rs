@@ -0,0 +1,68 @@
+use super::maze::{Maze, Wall};
+use ncollide2d::math::{Isometry, Vector};
+use ncollide2d::pipeline::object::{CollisionGroups, GeometricQueryType};
+use ncollide2d::shape::{Ball, Cuboid, ShapeHandle};
+use ncollide2d::world::CollisionWorld;
+
+// The maze and its walls as collision objects, the mouse is a ball.
+pub struct World {
+    pub maze: Maze,
+    pub world: CollisionWorld<f32, ()>,
+    pub ball: Ball<f32>,
+    wall: Cuboid<f32>,
+    pub active: CollisionGroups,
+    passive: CollisionGroups,
+    query_type: GeometricQueryType<f32>,
+}
+
+impl World {
+    pub fn new(maze: Maze) -> Self {
+        let mut active = CollisionGroups::new();
+        active.set_membership(&[1]);
+        let mut passive = CollisionGroups::new();
+        passive.set_membership(&[2]);
+        passive.set_whitelist(&[1]);
+        let mut world = World {
+            maze,
+            world: CollisionWorld::new(0.05),
+            ball: Ball::new(0.15),
+            wall: Cuboid::new(Vector::new(0.6, 0.1)),
+            active,
+            passive,
+            query_type: GeometricQueryType::Contacts(0.0, 0.0),
+        };
+        add_maze(&mut world);
+        world.world.update();
+        world
+    }
+}
+
+fn add_maze(state: &mut World) {
+    // Open rooms and staircases just have fewer walls, every wall (including
+    // the outer wall) comes from the same list the GUI draws.
+    for wall in state.maze.walls() {
+        add_wall(state, &wall);
+    }
+}
+
+fn add_wall(state: &mut World, wall: &Wall) {
+    let wall_shape = ShapeHandle::new(state.wall);
+    let angle;
+    let mut x = wall.x as f32;
+    let mut y = wall.y as f32;
+    if wall.vertical {
+        angle = std::f32::consts::PI / 2.0;
+        y += 0.5;
+    } else {
+        angle = 0.0;
+        x += 0.5;
+    }
+    let wall_position = Isometry::new(Vector::new(x, y), angle);
+    state.world.add(
+        wall_position,
+        wall_shape,
+        state.passive,
+        state.query_type,
+        (),
+    );
+}